@@ -0,0 +1,201 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! CPU computation of scene bounding boxes.
+
+use std::collections::HashSet;
+
+use peniko::kurbo::{Affine, Cap, Join, Rect, Shape};
+
+use super::{DrawTag, Encoding};
+
+/// Device space bounding boxes of an [`Encoding`].
+///
+/// Empty paths and draw objects whose bounds aren't known until resolve
+/// (glyph runs) have no bounding box.
+#[derive(Clone, Debug, Default)]
+pub struct SceneBboxes {
+    /// One bounding box per [`PathTag::PATH`](crate::PathTag::PATH), including
+    /// the stroke outline for stroked paths.
+    pub paths: Vec<Option<Rect>>,
+    /// One bounding box per draw tag: the bounding box of its path clipped
+    /// to the enclosing clips. An end clip has the bounding box of the
+    /// matching begin clip.
+    pub draws: Vec<Option<Rect>>,
+    /// One bounding box per clip element (begin or end clip) in stream
+    /// order: the intersection of the clip path with all enclosing clips.
+    pub clips: Vec<Option<Rect>>,
+    /// Union of all draw object bounding boxes.
+    pub scene: Option<Rect>,
+}
+
+impl Encoding {
+    /// Computes the bounding boxes of all paths and draw objects on the CPU.
+    ///
+    /// Stroked paths are expanded by half the stroke width, scaled up for
+    /// miter joins and square caps, so the result is conservative rather
+    /// than exact. The values are comparable to the [`DrawBbox`](crate::DrawBbox)
+    /// and [`ClipBbox`](crate::ClipBbox) computed by the GPU pipeline.
+    pub fn bboxes(&self) -> SceneBboxes {
+        let paths: Vec<_> = self
+            .decode_paths()
+            .map(|(path, transform, style)| {
+                if path.is_empty() {
+                    return None;
+                }
+                let transform = transform.to_kurbo();
+                let bbox = (transform * path).bounding_box();
+                Some(match style {
+                    peniko::Style::Fill(_) => bbox,
+                    peniko::Style::Stroke(stroke) => {
+                        let mut factor: f64 = 1.0;
+                        if stroke.join == Join::Miter {
+                            factor = factor.max(stroke.miter_limit);
+                        }
+                        if stroke.start_cap == Cap::Square || stroke.end_cap == Cap::Square {
+                            factor = factor.max(core::f64::consts::SQRT_2);
+                        }
+                        let radius = 0.5 * stroke.width * factor * max_scale(transform);
+                        bbox.inflate(radius, radius)
+                    }
+                })
+            })
+            .collect();
+        let glyph_run_draws: HashSet<_> = self
+            .resources
+            .glyph_runs
+            .iter()
+            .map(|run| run.stream_offsets.draw_tags)
+            .collect();
+        let mut path_bboxes = paths.iter().copied();
+        let mut draws = Vec::with_capacity(self.draw_tags.len());
+        let mut clips = Vec::with_capacity(self.n_clips as usize);
+        // Stack of enclosing clip bounding boxes, `None` meaning nothing is
+        // visible inside the clip.
+        let mut clip_stack: Vec<Option<Rect>> = Vec::new();
+        let mut scene: Option<Rect> = None;
+        for (ix, tag) in self.draw_tags.iter().enumerate() {
+            if glyph_run_draws.contains(&ix) {
+                draws.push(None);
+                continue;
+            }
+            let path_bbox = path_bboxes.next().flatten();
+            let clip = clip_stack.last().copied();
+            let clipped = match clip {
+                Some(clip) => path_bbox
+                    .zip(clip)
+                    .filter(|(a, b)| a.overlaps(*b))
+                    .map(|(a, b)| a.intersect(b)),
+                None => path_bbox,
+            };
+            let bbox = match *tag {
+                DrawTag::BEGIN_CLIP => {
+                    clip_stack.push(clipped);
+                    clips.push(clipped);
+                    clipped
+                }
+                DrawTag::END_CLIP => {
+                    let bbox = clip_stack.pop().flatten();
+                    clips.push(bbox);
+                    bbox
+                }
+                _ => clipped,
+            };
+            if let Some(bbox) = bbox {
+                scene = Some(scene.map_or(bbox, |scene| scene.union(bbox)));
+            }
+            draws.push(bbox);
+        }
+        SceneBboxes {
+            paths,
+            draws,
+            clips,
+            scene,
+        }
+    }
+}
+
+/// Returns the largest factor by which `transform` scales any vector.
+fn max_scale(transform: Affine) -> f64 {
+    let [a, b, c, d, _, _] = transform.as_coeffs();
+    // Largest singular value of the 2x2 linear part.
+    let sum = a * a + b * b + c * c + d * d;
+    let det = a * d - b * c;
+    (0.5 * (sum + (sum * sum - 4.0 * det * det).max(0.0).sqrt())).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::kurbo::{Affine, Cap, Join, Rect, Stroke};
+    use peniko::{Color, Fill, Mix};
+
+    use crate::{ClipBbox, Encoding, Transform};
+
+    #[test]
+    fn fills_and_strokes() {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::from_kurbo(&Affine::translate((10.0, 20.0))));
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 4.0, 2.0), true);
+        encoding.encode_color(Color::WHITE);
+        encoding.encode_transform(Transform::from_kurbo(&Affine::scale(2.0)));
+        let stroke = Stroke::new(2.0).with_join(Join::Bevel).with_caps(Cap::Butt);
+        assert!(encoding.encode_stroke_style(&stroke));
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 1.0, 1.0), false);
+        encoding.encode_color(Color::WHITE);
+        let stroke = stroke.with_join(Join::Miter).with_miter_limit(4.0);
+        assert!(encoding.encode_stroke_style(&stroke));
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 1.0, 1.0), false);
+        encoding.encode_color(Color::WHITE);
+
+        let bboxes = encoding.bboxes();
+        assert_eq!(bboxes.paths[0], Some(Rect::new(10.0, 20.0, 14.0, 22.0)));
+        // Half the stroke width, scaled by the transform.
+        assert_eq!(bboxes.paths[1], Some(Rect::new(-2.0, -2.0, 4.0, 4.0)));
+        // Miter joins extend up to the miter limit.
+        assert_eq!(bboxes.paths[2], Some(Rect::new(-8.0, -8.0, 10.0, 10.0)));
+        assert_eq!(bboxes.draws, bboxes.paths);
+        assert_eq!(bboxes.scene, Some(Rect::new(-8.0, -8.0, 14.0, 22.0)));
+    }
+
+    #[test]
+    fn clips() {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 10.0, 10.0), true);
+        encoding.encode_begin_clip(Mix::Clip.into(), 1.0);
+        encoding.encode_shape(&Rect::new(5.0, 5.0, 20.0, 20.0), true);
+        encoding.encode_begin_clip(Mix::Normal.into(), 1.0);
+        encoding.encode_shape(&Rect::new(-5.0, 0.0, 100.0, 8.0), true);
+        encoding.encode_color(Color::BLACK);
+        encoding.encode_end_clip();
+        encoding.encode_shape(&Rect::new(50.0, 50.0, 60.0, 60.0), true);
+        encoding.encode_color(Color::BLACK);
+        encoding.encode_end_clip();
+
+        let bboxes = encoding.bboxes();
+        let outer = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let inner = Rect::new(5.0, 5.0, 10.0, 10.0);
+        assert_eq!(bboxes.paths.len(), 6);
+        assert_eq!(bboxes.paths[3], None);
+        assert_eq!(
+            bboxes.draws,
+            [
+                Some(outer),
+                Some(inner),
+                Some(Rect::new(5.0, 5.0, 10.0, 8.0)),
+                Some(inner),
+                // Entirely clipped away.
+                None,
+                Some(outer),
+            ]
+        );
+        assert_eq!(
+            bboxes.clips,
+            [Some(outer), Some(inner), Some(inner), Some(outer)]
+        );
+        assert_eq!(bboxes.scene, Some(outer));
+        assert_eq!(ClipBbox::from(inner).bbox, [5.0, 5.0, 10.0, 10.0]);
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
 use bytemuck::{Pod, Zeroable};
+use peniko::kurbo::Rect;
 
 /// Clip stack element.
 ///
@@ -54,6 +55,14 @@ pub struct ClipBbox {
     pub bbox: [f32; 4],
 }
 
+impl From<Rect> for ClipBbox {
+    fn from(rect: Rect) -> Self {
+        Self {
+            bbox: [rect.x0, rect.y0, rect.x1, rect.y1].map(|x| x as f32),
+        }
+    }
+}
+
 impl ClipBic {
     pub fn new(a: u32, b: u32) -> Self {
         Self { a, b }
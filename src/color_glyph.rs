@@ -0,0 +1,659 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Color glyphs from `COLR`, `sbix` and `CBDT` tables.
+//!
+//! Color glyphs can't be expanded into a single path at resolve time, so they
+//! are encoded as regular draw objects when the glyph run is encoded.
+
+use core::f32::consts::PI;
+
+use peniko::color::palette;
+use peniko::kurbo::{Affine, BezPath, Point, Rect, Shape};
+use peniko::{
+    BlendMode, Blob, Brush, BrushRef, Color, ColorStop, Compose, Extend, Fill, Font, Gradient,
+    Image, ImageFormat, Mix,
+};
+use ttf_parser::colr::{CompositeMode, GradientExtend, Paint, Painter};
+use ttf_parser::{GlyphId, RasterGlyphImage, RasterImageFormat, RgbaColor};
+
+use super::{Encoding, Glyph, GlyphRun, NormalizedCoord, Transform};
+use crate::outline::set_variations;
+
+/// Face of a font with color glyph tables.
+pub(crate) struct ColorFace<'a> {
+    face: ttf_parser::Face<'a>,
+    /// Requested size of bitmap strikes.
+    ppem: u16,
+}
+
+impl<'a> ColorFace<'a> {
+    /// Parses the face of `font` for drawing glyphs at `font_size` pixels per
+    /// em.
+    ///
+    /// Returns `None` if the font fails to parse or has no color glyph
+    /// tables, in which case all glyphs are outline glyphs.
+    pub(crate) fn new(font: &'a Font, coords: &[NormalizedCoord], font_size: f32) -> Option<Self> {
+        let mut face = ttf_parser::Face::parse(font.data.data(), font.index).ok()?;
+        let tables = face.tables();
+        if tables.colr.is_none() && tables.sbix.is_none() && tables.cbdt.is_none() {
+            return None;
+        }
+        set_variations(&mut face, coords);
+        Some(Self {
+            face,
+            ppem: font_size.ceil().clamp(1.0, f32::from(u16::MAX)) as u16,
+        })
+    }
+
+    /// Returns true if the glyph has a color layer description or a bitmap.
+    pub(crate) fn is_color_glyph(&self, glyph_id: u32) -> bool {
+        let Ok(id) = u16::try_from(glyph_id) else {
+            return false;
+        };
+        self.face.is_color_glyph(GlyphId(id))
+            || self
+                .face
+                .glyph_raster_image(GlyphId(id), self.ppem)
+                .is_some()
+    }
+
+    /// Encodes a color glyph placed like the outline glyphs of `run`.
+    ///
+    /// Layers of `COLR` glyphs are preferred over bitmaps. The brush provides
+    /// the foreground color if it is solid and `alpha` applies to the glyph
+    /// as a whole.
+    pub(crate) fn encode(
+        &self,
+        encoding: &mut Encoding,
+        run: &GlyphRun,
+        glyph: &Glyph,
+        brush: BrushRef<'_>,
+        alpha: f32,
+    ) {
+        let Ok(id) = u16::try_from(glyph.id) else {
+            return;
+        };
+        let id = GlyphId(id);
+        let glyph_transform = run
+            .effective_glyph_transform()
+            .unwrap_or(Transform::IDENTITY);
+        // Maps the y-up glyph space in pixels to the space of the run, as the
+        // resolver does for outlines.
+        let transform = run.transform.to_kurbo()
+            * Affine::new([1.0, 0.0, 0.0, -1.0, glyph.x.into(), glyph.y.into()])
+            * glyph_transform.to_kurbo();
+        let foreground = match brush {
+            BrushRef::Solid(color) => color,
+            _ => palette::css::BLACK,
+        }
+        .to_rgba8();
+        let foreground = RgbaColor::new(foreground.r, foreground.g, foreground.b, foreground.a);
+        if self.face.is_color_glyph(id) {
+            let scale = run.font_size / f32::from(self.face.units_per_em());
+            let units = transform * Affine::scale(scale.into());
+            let bbox = self.face.global_bounding_box();
+            let area = Rect::new(
+                bbox.x_min.into(),
+                bbox.y_min.into(),
+                bbox.x_max.into(),
+                bbox.y_max.into(),
+            );
+            let depth = encoding.n_open_clips;
+            if alpha < 1.0 {
+                encoding.encode_layer(
+                    Mix::Normal.into(),
+                    alpha,
+                    &area,
+                    Transform::from_kurbo(&units),
+                );
+            }
+            let mut painter = ColorPainter {
+                encoding,
+                face: &self.face,
+                transforms: vec![units],
+                outline: BezPath::new(),
+                outline_transform: units,
+                clips: Vec::new(),
+                area,
+                depth: 0,
+            };
+            self.face.paint_color_glyph(id, 0, foreground, &mut painter);
+            while encoding.n_open_clips > depth {
+                encoding.encode_end_clip();
+            }
+        } else if let Some(raster) = self.face.glyph_raster_image(id, self.ppem) {
+            let Some(image) = decode_raster(&raster, foreground) else {
+                return;
+            };
+            let scale = run.font_size / f32::from(raster.pixels_per_em.max(1));
+            // The offset is the bottom left corner of the image.
+            let placement = transform
+                * Affine::scale(scale.into())
+                * Affine::new([
+                    1.0,
+                    0.0,
+                    0.0,
+                    -1.0,
+                    raster.x.into(),
+                    f64::from(raster.y) + f64::from(image.height),
+                ]);
+            encoding.encode_transform(Transform::from_kurbo(&placement));
+            encoding.encode_fill_style(Fill::NonZero);
+            let rect = Rect::new(0.0, 0.0, image.width.into(), image.height.into());
+            if encoding.encode_shape(&rect, true) {
+                encoding.encode_image(&image, alpha);
+            }
+        }
+    }
+}
+
+/// Encodes the layers of a `COLR` glyph.
+///
+/// `COLRv0` paints fill the most recent outline and `COLRv1` paints fill the
+/// innermost clip, or the whole area outside of clips, with the brush mapped
+/// through the current transform. Clips, clip boxes and composite layers
+/// become clip layers in the encoding.
+struct ColorPainter<'a> {
+    encoding: &'a mut Encoding,
+    face: &'a ttf_parser::Face<'a>,
+    /// Transforms from font units to the run space, starting with the glyph
+    /// transform.
+    transforms: Vec<Affine>,
+    /// Outline of the last glyph that wasn't pushed as a clip yet.
+    outline: BezPath,
+    outline_transform: Affine,
+    /// Shapes of the open clips and clip boxes with their transforms,
+    /// innermost last.
+    clips: Vec<(BezPath, Affine)>,
+    /// Area in font units that covers every glyph of the font.
+    area: Rect,
+    /// Number of layers opened by the painter.
+    depth: u32,
+}
+
+impl ColorPainter<'_> {
+    fn transform(&self) -> Affine {
+        self.transforms[self.transforms.len() - 1]
+    }
+
+    fn pop(&mut self) {
+        if self.depth > 0 {
+            self.depth -= 1;
+            self.encoding.encode_end_clip();
+        }
+    }
+}
+
+impl<'a> Painter<'a> for ColorPainter<'a> {
+    fn outline_glyph(&mut self, glyph_id: GlyphId) {
+        self.outline.truncate(0);
+        self.face
+            .outline_glyph(glyph_id, &mut BezPathBuilder(&mut self.outline));
+        self.outline_transform = self.transform();
+    }
+
+    fn paint(&mut self, paint: Paint<'a>) {
+        let coords = self.face.variation_coordinates();
+        let brush = match paint {
+            Paint::Solid(color) => Brush::Solid(to_color(color)),
+            Paint::LinearGradient(gradient) => {
+                // The gradient vector is the projection of p0p1 onto the
+                // normal of p0p2.
+                let p0 = Point::new(gradient.x0.into(), gradient.y0.into());
+                let p1 = Point::new(gradient.x1.into(), gradient.y1.into());
+                let p2 = Point::new(gradient.x2.into(), gradient.y2.into());
+                let normal = (p2 - p0).turn_90();
+                let end = if normal.hypot2() == 0.0 {
+                    p1
+                } else {
+                    p0 + normal * ((p1 - p0).dot(normal) / normal.hypot2())
+                };
+                let stops = to_stops(gradient.stops(0, coords));
+                to_brush(Gradient::new_linear(p0, end), gradient.extend, &stops)
+            }
+            Paint::RadialGradient(gradient) => {
+                let stops = to_stops(gradient.stops(0, coords));
+                to_brush(
+                    Gradient::new_two_point_radial(
+                        (gradient.x0, gradient.y0),
+                        gradient.r0,
+                        (gradient.x1, gradient.y1),
+                        gradient.r1,
+                    ),
+                    gradient.extend,
+                    &stops,
+                )
+            }
+            Paint::SweepGradient(gradient) => {
+                // Angles are in units of 180 degrees.
+                let stops = to_stops(gradient.stops(0, coords));
+                to_brush(
+                    Gradient::new_sweep(
+                        (gradient.center_x, gradient.center_y),
+                        gradient.start_angle * PI,
+                        gradient.end_angle * PI,
+                    ),
+                    gradient.extend,
+                    &stops,
+                )
+            }
+        };
+        // Paints that aren't enclosed by a glyph or clip box cover the whole
+        // area.
+        let (shape, shape_transform) = if !self.outline.elements().is_empty() {
+            (self.outline.clone(), self.outline_transform)
+        } else if let Some((clip, transform)) = self.clips.last() {
+            (clip.clone(), *transform)
+        } else {
+            (self.area.to_path(0.1), self.transforms[0])
+        };
+        if shape_transform.determinant() == 0.0 {
+            return;
+        }
+        let brush_transform = shape_transform.inverse() * self.transform();
+        self.encoding
+            .encode_transform(Transform::from_kurbo(&shape_transform));
+        self.encoding.encode_fill_style(Fill::NonZero);
+        if self.encoding.encode_shape(&shape, true) {
+            self.encoding.encode_brush_with_transform(
+                &brush,
+                1.0,
+                Transform::from_kurbo(&brush_transform),
+            );
+        }
+    }
+
+    fn push_clip(&mut self) {
+        self.depth += 1;
+        let outline = core::mem::take(&mut self.outline);
+        self.encoding.encode_layer(
+            Mix::Clip.into(),
+            1.0,
+            &outline,
+            Transform::from_kurbo(&self.outline_transform),
+        );
+        self.clips.push((outline, self.outline_transform));
+    }
+
+    fn push_clip_box(&mut self, clipbox: ttf_parser::colr::ClipBox) {
+        self.depth += 1;
+        let rect = Rect::new(
+            clipbox.x_min.into(),
+            clipbox.y_min.into(),
+            clipbox.x_max.into(),
+            clipbox.y_max.into(),
+        );
+        self.encoding.encode_layer(
+            Mix::Clip.into(),
+            1.0,
+            &rect,
+            Transform::from_kurbo(&self.transform()),
+        );
+        self.clips.push((rect.to_path(0.1), self.transform()));
+    }
+
+    fn pop_clip(&mut self) {
+        self.clips.pop();
+        self.pop();
+    }
+
+    fn push_layer(&mut self, mode: CompositeMode) {
+        self.depth += 1;
+        self.encoding.encode_layer(
+            to_blend_mode(mode),
+            1.0,
+            &self.area,
+            Transform::from_kurbo(&self.transforms[0]),
+        );
+    }
+
+    fn pop_layer(&mut self) {
+        self.pop();
+    }
+
+    fn push_transform(&mut self, transform: ttf_parser::Transform) {
+        let ttf_parser::Transform { a, b, c, d, e, f } = transform;
+        let transform = Affine::new([a, b, c, d, e, f].map(f64::from));
+        self.transforms.push(self.transform() * transform);
+    }
+
+    fn pop_transform(&mut self) {
+        if self.transforms.len() > 1 {
+            self.transforms.pop();
+        }
+    }
+}
+
+/// Collects a glyph outline into a path.
+struct BezPathBuilder<'a>(&'a mut BezPath);
+
+impl ttf_parser::OutlineBuilder for BezPathBuilder<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to((x, y));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to((x, y));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to((x1, y1), (x, y));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.curve_to((x1, y1), (x2, y2), (x, y));
+    }
+
+    fn close(&mut self) {
+        self.0.close_path();
+    }
+}
+
+fn to_color(color: RgbaColor) -> Color {
+    Color::from_rgba8(color.red, color.green, color.blue, color.alpha)
+}
+
+fn to_stops(stops: impl Iterator<Item = ttf_parser::colr::ColorStop>) -> Vec<ColorStop> {
+    stops
+        .map(|stop| ColorStop::from((stop.stop_offset, to_color(stop.color))))
+        .collect()
+}
+
+fn to_brush(mut gradient: Gradient, extend: GradientExtend, stops: &[ColorStop]) -> Brush {
+    gradient.extend = match extend {
+        GradientExtend::Pad => Extend::Pad,
+        GradientExtend::Repeat => Extend::Repeat,
+        GradientExtend::Reflect => Extend::Reflect,
+    };
+    gradient.with_stops(stops).into()
+}
+
+fn to_blend_mode(mode: CompositeMode) -> BlendMode {
+    let compose = |compose| BlendMode::new(Mix::Normal, compose);
+    match mode {
+        CompositeMode::Clear => compose(Compose::Clear),
+        CompositeMode::Source => compose(Compose::Copy),
+        CompositeMode::Destination => compose(Compose::Dest),
+        CompositeMode::SourceOver => compose(Compose::SrcOver),
+        CompositeMode::DestinationOver => compose(Compose::DestOver),
+        CompositeMode::SourceIn => compose(Compose::SrcIn),
+        CompositeMode::DestinationIn => compose(Compose::DestIn),
+        CompositeMode::SourceOut => compose(Compose::SrcOut),
+        CompositeMode::DestinationOut => compose(Compose::DestOut),
+        CompositeMode::SourceAtop => compose(Compose::SrcAtop),
+        CompositeMode::DestinationAtop => compose(Compose::DestAtop),
+        CompositeMode::Xor => compose(Compose::Xor),
+        CompositeMode::Plus => compose(Compose::Plus),
+        CompositeMode::Screen => Mix::Screen.into(),
+        CompositeMode::Overlay => Mix::Overlay.into(),
+        CompositeMode::Darken => Mix::Darken.into(),
+        CompositeMode::Lighten => Mix::Lighten.into(),
+        CompositeMode::ColorDodge => Mix::ColorDodge.into(),
+        CompositeMode::ColorBurn => Mix::ColorBurn.into(),
+        CompositeMode::HardLight => Mix::HardLight.into(),
+        CompositeMode::SoftLight => Mix::SoftLight.into(),
+        CompositeMode::Difference => Mix::Difference.into(),
+        CompositeMode::Exclusion => Mix::Exclusion.into(),
+        CompositeMode::Multiply => Mix::Multiply.into(),
+        CompositeMode::Hue => Mix::Hue.into(),
+        CompositeMode::Saturation => Mix::Saturation.into(),
+        CompositeMode::Color => Mix::Color.into(),
+        CompositeMode::Luminosity => Mix::Luminosity.into(),
+    }
+}
+
+/// Decodes a bitmap glyph into an RGBA image with straight alpha.
+///
+/// Grayscale and monochrome bitmaps are coverage masks, which are tinted
+/// with the foreground color.
+fn decode_raster(raster: &RasterGlyphImage<'_>, foreground: RgbaColor) -> Option<Image> {
+    let (width, height) = (u32::from(raster.width), u32::from(raster.height));
+    let (bits, packed) = match raster.format {
+        RasterImageFormat::PNG => return decode_png(raster.data),
+        RasterImageFormat::BitmapPremulBgra32 => {
+            let n_bytes = width as usize * height as usize * 4;
+            let data = raster
+                .data
+                .get(..n_bytes)?
+                .chunks_exact(4)
+                .flat_map(|bgra| {
+                    let [b, g, r, a] = [bgra[0], bgra[1], bgra[2], bgra[3]];
+                    // Malformed data may have color channels larger than alpha.
+                    let unpremultiply = |c: u8| {
+                        (u32::from(c) * 255)
+                            .checked_div(u32::from(a))
+                            .map_or(0, |c| c.min(255)) as u8
+                    };
+                    [unpremultiply(r), unpremultiply(g), unpremultiply(b), a]
+                })
+                .collect::<Vec<_>>();
+            return Some(Image::new(
+                Blob::from(data),
+                ImageFormat::Rgba8,
+                width,
+                height,
+            ));
+        }
+        RasterImageFormat::BitmapMono => (1, false),
+        RasterImageFormat::BitmapMonoPacked => (1, true),
+        RasterImageFormat::BitmapGray2 => (2, false),
+        RasterImageFormat::BitmapGray2Packed => (2, true),
+        RasterImageFormat::BitmapGray4 => (4, false),
+        RasterImageFormat::BitmapGray4Packed => (4, true),
+        RasterImageFormat::BitmapGray8 => (8, false),
+    };
+    let coverage = decode_coverage(raster.data, width as usize, height as usize, bits, packed)?;
+    let data = coverage
+        .into_iter()
+        .flat_map(|coverage| {
+            let alpha = u32::from(coverage) * u32::from(foreground.alpha) / 255;
+            [
+                foreground.red,
+                foreground.green,
+                foreground.blue,
+                alpha as u8,
+            ]
+        })
+        .collect::<Vec<_>>();
+    Some(Image::new(
+        Blob::from(data),
+        ImageFormat::Rgba8,
+        width,
+        height,
+    ))
+}
+
+/// Decodes a coverage mask with `bits` per pixel, stored most significant
+/// bit first. Rows of packed masks aren't padded to whole bytes.
+fn decode_coverage(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    bits: usize,
+    packed: bool,
+) -> Option<Vec<u8>> {
+    let row_bits = width * bits;
+    let stride = if packed {
+        row_bits
+    } else {
+        row_bits.next_multiple_of(8)
+    };
+    if data.len() * 8 < stride * height {
+        return None;
+    }
+    let max = (1_u32 << bits) - 1;
+    let coverage = (0..height)
+        .flat_map(|y| (0..width).map(move |x| y * stride + x * bits))
+        .map(|bit| {
+            let byte = u32::from(data[bit / 8]);
+            let value = (byte >> (8 - bits - bit % 8)) & max;
+            (value * 255 / max) as u8
+        })
+        .collect();
+    Some(coverage)
+}
+
+/// Decodes a PNG into an RGBA image with straight alpha.
+fn decode_png(data: &[u8]) -> Option<Image> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().ok()?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).ok()?;
+    buffer.truncate(info.buffer_size());
+    let data = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buffer
+            .chunks_exact(2)
+            .flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]])
+            .collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        // Palettes are expanded by the decoder.
+        png::ColorType::Indexed => return None,
+    };
+    Some(Image::new(
+        Blob::from(data),
+        ImageFormat::Rgba8,
+        info.width,
+        info.height,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::color::palette;
+    use peniko::kurbo::{Affine, Rect, Shape};
+    use peniko::{Blob, Fill, Font};
+
+    use super::decode_coverage;
+    use crate::test_font::TestFont;
+    use crate::{DrawColor, DrawTag, Encoding, Glyph, Patch, Transform};
+
+    fn color_font() -> Font {
+        Font::new(
+            Blob::from(TestFont::new(1000).with_color_glyphs().build()),
+            0,
+        )
+    }
+
+    fn encode(font: &Font, font_size: f32, glyphs: &[(u32, f32)]) -> Encoding {
+        let mut encoding = Encoding::new();
+        let glyphs = glyphs.iter().map(|&(id, x)| Glyph { id, x, y: 30.0 });
+        encoding
+            .encode_glyph_run(
+                font,
+                font_size,
+                Transform::IDENTITY,
+                None,
+                Fill::NonZero,
+                palette::css::LIME,
+                false,
+                &[],
+            )
+            .glyphs(glyphs);
+        encoding
+    }
+
+    #[test]
+    fn colr_v0_layers() {
+        let font = color_font();
+        let glyphs = [
+            (TestFont::SQUARE, 0.0),
+            (TestFont::COLR_V0, 10.0),
+            (TestFont::TRIANGLE, 20.0),
+        ];
+        let encoding = encode(&font, 10.0, &glyphs);
+        let resources = &encoding.resources;
+        // The color glyph splits the run around its layers.
+        assert_eq!(resources.glyph_runs.len(), 2);
+        assert_eq!(resources.glyph_runs[0].glyphs, 0..1);
+        assert_eq!(resources.glyph_runs[1].glyphs, 1..2);
+        assert!(matches!(
+            resources.patches[..],
+            [Patch::GlyphRun { index: 0 }, Patch::GlyphRun { index: 1 }]
+        ));
+        assert!(encoding.draw_tags == [DrawTag::COLOR; 4]);
+        let [red, lime] = [palette::css::RED, palette::css::LIME].map(|c| DrawColor::from(c).rgba);
+        assert_eq!(encoding.draw_data, [lime, red, lime, lime]);
+    }
+
+    #[test]
+    fn colr_v1_paints() {
+        let font = color_font();
+        let encoding = encode(&font, 10.0, &[(TestFont::COLR_V1, 0.0)]);
+        assert!(encoding.resources.glyph_runs.is_empty());
+        assert_eq!(encoding.n_open_clips, 0);
+        let tags = &encoding.draw_tags;
+        let count = |tag| tags.iter().filter(|&&t| t == tag).count();
+        // Clip box, composite layers and one clip per glyph.
+        assert_eq!(count(DrawTag::BEGIN_CLIP), 5);
+        assert_eq!(count(DrawTag::END_CLIP), 5);
+        assert_eq!(count(DrawTag::LINEAR_GRADIENT), 1);
+        assert_eq!(count(DrawTag::COLOR), 1);
+        assert!(tags[0] == DrawTag::BEGIN_CLIP);
+        assert!(matches!(
+            encoding.resources.patches[..],
+            [Patch::Ramp { .. }]
+        ));
+        // The clip box is in font units scaled to the font size.
+        let scale = Affine::new([0.01, 0.0, 0.0, -0.01, 0.0, 30.0]);
+        assert_eq!(encoding.transforms[0], Transform::from_kurbo(&scale));
+    }
+
+    #[test]
+    fn colr_v1_unclipped_layer() {
+        let font = color_font();
+        let encoding = encode(&font, 10.0, &[(TestFont::COLR_V1_LAYERS, 0.0)]);
+        assert!(
+            encoding.draw_tags
+                == [
+                    DrawTag::BEGIN_CLIP,
+                    DrawTag::COLOR,
+                    DrawTag::END_CLIP,
+                    DrawTag::COLOR
+                ]
+        );
+        let [red, blue] = [palette::css::RED, palette::css::BLUE].map(|c| DrawColor::from(c).rgba);
+        // The begin clip is followed by its blend mode and alpha.
+        assert_eq!(encoding.draw_data[2..], [red, blue]);
+        // The paint after the glyph layer covers the whole area rather than
+        // the square.
+        let (area, ..) = encoding.decode_paths().last().unwrap();
+        assert_eq!(area.bounding_box(), Rect::new(0.0, 0.0, 1000.0, 1000.0));
+    }
+
+    #[test]
+    fn bitmap_glyph() {
+        let font = color_font();
+        let encoding = encode(&font, 20.0, &[(TestFont::BITMAP, 10.0)]);
+        assert!(encoding.resources.glyph_runs.is_empty());
+        assert!(encoding.draw_tags == [DrawTag::IMAGE]);
+        let [Patch::Image { image, .. }] = &encoding.resources.patches[..] else {
+            panic!("expected a single image patch");
+        };
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.data.data(), TestFont::BITMAP_PIXELS);
+        // The image sits on the baseline with its rows running down.
+        let placement = Affine::translate((10.0, 28.0));
+        assert_eq!(
+            encoding.transforms.last(),
+            Some(&Transform::from_kurbo(&placement))
+        );
+    }
+
+    #[test]
+    fn coverage_masks() {
+        // Two rows of three 2 bit pixels.
+        let padded = [0b1101_0000, 0b0010_1100];
+        let packed = [0b1101_0000, 0b1011_0000];
+        let expected = [255, 85, 0, 0, 170, 255];
+        assert_eq!(decode_coverage(&padded, 3, 2, 2, false).unwrap(), expected);
+        assert_eq!(decode_coverage(&packed, 3, 2, 2, true).unwrap(), expected);
+        assert_eq!(decode_coverage(&packed[..1], 3, 2, 2, true), None);
+    }
+}
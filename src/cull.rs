@@ -0,0 +1,335 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Viewport culling of encoded scenes.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use peniko::kurbo::Rect;
+
+use super::{DrawTag, Encoding, Patch, PathTag};
+
+impl Encoding {
+    /// Returns a copy of the encoding without the draw objects that can't
+    /// contribute to the given device space viewport.
+    ///
+    /// A draw object is dropped if its bounding box, as computed by
+    /// [`Encoding::bboxes`], is empty or doesn't overlap the viewport. A clip
+    /// or layer is dropped together with everything inside it. Glyph runs
+    /// have no bounds before resolve and are only dropped with an enclosing
+    /// clip.
+    ///
+    /// The transform and style streams are kept as is, and patches and glyph
+    /// run stream offsets are rewritten to match the remaining streams.
+    pub fn cull(&self, viewport: Rect) -> Self {
+        // Maps the indices of glyph run draw objects to their glyph runs.
+        let glyph_runs: HashMap<usize, usize> = self
+            .resources
+            .glyph_runs
+            .iter()
+            .enumerate()
+            .map(|(ix, run)| (run.stream_offsets.draw_tags, ix))
+            .collect();
+        let keep = self.visible_draws(viewport, &glyph_runs);
+        let mut out = Self {
+            transforms: self.transforms.clone(),
+            styles: self.styles.clone(),
+            flags: self.flags,
+            i16_tolerance: self.i16_tolerance,
+            ..Self::default()
+        };
+        out.resources.color_stops = self.resources.color_stops.clone();
+        out.resources.glyphs = self.resources.glyphs.clone();
+        out.resources.normalized_coords = self.resources.normalized_coords.clone();
+        let mut paths = PathUnits {
+            encoding: self,
+            tag_ix: 0,
+            data_ix: 0,
+        };
+        // Maps from old to new draw data offsets and glyph run indices.
+        let mut draw_data_offsets = HashMap::new();
+        let mut glyph_run_indices = HashMap::new();
+        let mut draw_data_ix = 0;
+        for (draw_ix, (&tag, &keep)) in self.draw_tags.iter().zip(&keep).enumerate() {
+            let size = tag.scene_size() as usize;
+            let data = draw_data_ix..draw_data_ix + size;
+            draw_data_ix += size;
+            if let Some(&run_ix) = glyph_runs.get(&draw_ix) {
+                let run = &self.resources.glyph_runs[run_ix];
+                paths.flush_to(&mut out, run.stream_offsets.path_tags);
+                if keep {
+                    let mut run = run.clone();
+                    run.stream_offsets.path_tags = out.path_tags.len();
+                    run.stream_offsets.path_data = out.path_data.len();
+                    run.stream_offsets.draw_tags = out.draw_tags.len();
+                    run.stream_offsets.draw_data = out.draw_data.len();
+                    glyph_run_indices.insert(run_ix, out.resources.glyph_runs.len());
+                    out.resources.glyph_runs.push(run);
+                }
+            } else {
+                paths.unit(&mut out, keep);
+            }
+            if keep {
+                draw_data_offsets.insert(data.start, out.draw_data.len());
+                out.draw_tags.push(tag);
+                if let Some(data) = self.draw_data.get(data) {
+                    out.draw_data.extend_from_slice(data);
+                }
+                match tag {
+                    DrawTag::BEGIN_CLIP => {
+                        out.n_clips += 1;
+                        out.n_open_clips += 1;
+                    }
+                    DrawTag::END_CLIP => {
+                        out.n_clips += 1;
+                        out.n_open_clips = out.n_open_clips.saturating_sub(1);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        paths.flush_to(&mut out, self.path_tags.len());
+        // Anything left belongs to paths without a draw object.
+        while paths.tag_ix < self.path_tags.len() {
+            paths.unit(&mut out, true);
+        }
+        out.resources.patches = self
+            .resources
+            .patches
+            .iter()
+            .filter_map(|patch| match patch {
+                Patch::Ramp {
+                    draw_data_offset,
+                    stops,
+                    extend,
+                } => Some(Patch::Ramp {
+                    draw_data_offset: *draw_data_offsets.get(draw_data_offset)?,
+                    stops: stops.clone(),
+                    extend: *extend,
+                }),
+                Patch::GlyphRun { index } => Some(Patch::GlyphRun {
+                    index: *glyph_run_indices.get(index)?,
+                }),
+                Patch::Image {
+                    draw_data_offset,
+                    image,
+                    alpha_multiplier,
+                } => Some(Patch::Image {
+                    draw_data_offset: *draw_data_offsets.get(draw_data_offset)?,
+                    image: image.clone(),
+                    alpha_multiplier: *alpha_multiplier,
+                }),
+            })
+            .collect();
+        out
+    }
+
+    /// Returns whether each draw object is visible in the viewport, given
+    /// the draw objects of glyph runs.
+    fn visible_draws(&self, viewport: Rect, glyph_runs: &HashMap<usize, usize>) -> Vec<bool> {
+        let bboxes = self.bboxes();
+        let is_visible = |bbox: Option<Rect>| bbox.is_some_and(|bbox| bbox.overlaps(viewport));
+        // Visibility of the enclosing clips.
+        let mut clip_stack = Vec::new();
+        let mut keep = Vec::with_capacity(self.draw_tags.len());
+        for (ix, (tag, bbox)) in self.draw_tags.iter().zip(&bboxes.draws).enumerate() {
+            let parent = clip_stack.last().copied().unwrap_or(true);
+            keep.push(match *tag {
+                DrawTag::BEGIN_CLIP => {
+                    let visible = parent && is_visible(*bbox);
+                    clip_stack.push(visible);
+                    visible
+                }
+                DrawTag::END_CLIP => clip_stack.pop().unwrap_or(true),
+                _ if glyph_runs.contains_key(&ix) => parent,
+                _ => parent && is_visible(*bbox),
+            });
+        }
+        keep
+    }
+}
+
+/// Reader that splits the path streams into the tags and data of
+/// individual paths.
+struct PathUnits<'a> {
+    encoding: &'a Encoding,
+    tag_ix: usize,
+    data_ix: usize,
+}
+
+impl PathUnits<'_> {
+    /// Copies transform and style tags up to `end`, stopping at the first
+    /// segment.
+    fn flush_to(&mut self, out: &mut Encoding, end: usize) {
+        while self.tag_ix < end {
+            let tag = self.encoding.path_tags[self.tag_ix];
+            if tag.is_path_segment() || tag == PathTag::PATH {
+                break;
+            }
+            out.path_tags.push(tag);
+            self.tag_ix += 1;
+        }
+    }
+
+    /// Consumes the next path up to and including its [`PathTag::PATH`].
+    ///
+    /// If `keep` is false, only the transform and style tags are copied.
+    fn unit(&mut self, out: &mut Encoding, keep: bool) {
+        let tags = &self.encoding.path_tags;
+        let start = self.tag_ix;
+        let mut data_size = 0;
+        while let Some(&tag) = tags.get(self.tag_ix) {
+            self.tag_ix += 1;
+            if tag == PathTag::PATH {
+                break;
+            }
+            if tag.is_path_segment() {
+                data_size += tag.data_size();
+            }
+        }
+        let data: Range<usize> = self.data_ix..self.data_ix + data_size;
+        self.data_ix = data.end;
+        for &tag in &tags[start..self.tag_ix] {
+            if keep {
+                if tag == PathTag::PATH {
+                    out.n_paths += 1;
+                } else if tag.is_path_segment() {
+                    out.n_path_segments += 1;
+                }
+                out.path_tags.push(tag);
+            } else if matches!(tag, PathTag::TRANSFORM | PathTag::STYLE) {
+                out.path_tags.push(tag);
+            }
+        }
+        if let Some(data) = self.encoding.path_data.get(data).filter(|_| keep) {
+            out.path_data.extend_from_slice(data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use peniko::kurbo::{Affine, Rect};
+    use peniko::{Blob, Color, ColorStop, Fill, Font, Gradient, Mix};
+
+    use crate::{Encoding, Glyph, GlyphRun, Patch, Transform};
+
+    fn push_glyph_run(encoding: &mut Encoding) {
+        let stream_offsets = encoding.stream_offsets();
+        let glyphs = encoding.resources.glyphs.len();
+        encoding.resources.glyphs.push(Glyph {
+            id: 1,
+            x: 0.0,
+            y: 0.0,
+        });
+        let index = encoding.resources.glyph_runs.len();
+        encoding.resources.glyph_runs.push(GlyphRun {
+            font: Font::new(Blob::new(Arc::new([0_u8; 4])), 0),
+            transform: Transform::IDENTITY,
+            glyph_transform: None,
+            brush_transform: None,
+            font_size: 12.0,
+            hint: false,
+            embolden: 0.0,
+            skew: 0.0,
+            normalized_coords: 0..0,
+            style: Fill::NonZero.into(),
+            glyphs: glyphs..glyphs + 1,
+            stream_offsets,
+            buffer: None,
+        });
+        encoding.resources.patches.push(Patch::GlyphRun { index });
+        encoding.encode_color(Color::BLACK);
+    }
+
+    fn gradient() -> Gradient {
+        Gradient::new_linear((0.0, 0.0), (1.0, 0.0)).with_stops([
+            ColorStop::from((0.0, Color::BLACK)),
+            ColorStop::from((1.0, Color::WHITE)),
+        ])
+    }
+
+    fn scene() -> Encoding {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        // Off screen.
+        encoding.encode_shape(&Rect::new(200.0, 0.0, 210.0, 10.0), true);
+        encoding.encode_brush(&gradient(), 1.0);
+        // On screen, but behind a transform that moves it off screen.
+        encoding.encode_transform(Transform::from_kurbo(&Affine::translate((-50.0, 0.0))));
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 10.0, 10.0), true);
+        encoding.encode_color(Color::WHITE);
+        encoding.encode_transform(Transform::IDENTITY);
+        // An off screen layer containing a glyph run.
+        encoding.encode_shape(&Rect::new(0.0, 200.0, 10.0, 210.0), true);
+        encoding.encode_begin_clip(Mix::Normal.into(), 1.0);
+        push_glyph_run(&mut encoding);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 500.0, 500.0), true);
+        encoding.encode_color(Color::WHITE);
+        encoding.encode_end_clip();
+        // Visible.
+        encoding.encode_shape(&Rect::new(10.0, 10.0, 20.0, 20.0), true);
+        encoding.encode_brush(&gradient(), 1.0);
+        push_glyph_run(&mut encoding);
+        encoding.encode_shape(&Rect::new(90.0, 90.0, 110.0, 110.0), true);
+        encoding.encode_color(Color::WHITE);
+        encoding
+    }
+
+    #[test]
+    fn cull() {
+        let mut encoding = scene();
+        encoding.i16_tolerance = Some(0.5);
+        assert!(encoding.validate().is_ok());
+        let culled = encoding.cull(Rect::new(0.0, 0.0, 100.0, 100.0));
+        assert_eq!(culled.validate(), Ok(()));
+        assert_eq!(culled.i16_tolerance, Some(0.5));
+        assert_eq!(culled.n_paths, 2);
+        assert_eq!(culled.n_clips, 0);
+        assert_eq!(culled.draw_tags.len(), 3);
+        assert_eq!(culled.transforms, encoding.transforms);
+        assert_eq!(culled.styles, encoding.styles);
+        let bboxes: Vec<_> = culled.bboxes().paths.into_iter().flatten().collect();
+        assert_eq!(
+            bboxes,
+            [
+                Rect::new(10.0, 10.0, 20.0, 20.0),
+                Rect::new(90.0, 90.0, 110.0, 110.0)
+            ]
+        );
+        // The remaining gradient and glyph run are patched at their new
+        // offsets.
+        assert!(matches!(
+            culled.resources.patches[..],
+            [
+                Patch::Ramp {
+                    draw_data_offset: 0,
+                    ..
+                },
+                Patch::GlyphRun { index: 0 }
+            ]
+        ));
+        let run = &culled.resources.glyph_runs[0];
+        assert_eq!(run.glyphs, 1..2);
+        assert_eq!(run.stream_offsets.draw_tags, 1);
+        assert_eq!(run.stream_offsets.draw_data, 5);
+        assert_eq!(run.stream_offsets.path_tags, culled.path_tags.len() - 5);
+        assert_eq!(run.stream_offsets.path_data, culled.path_data.len() - 10);
+    }
+
+    #[test]
+    fn visible_clips_are_kept() {
+        let encoding = scene();
+        let culled = encoding.cull(Rect::new(-1000.0, -1000.0, 1000.0, 1000.0));
+        assert!(culled.path_tags == encoding.path_tags);
+        assert_eq!(culled.path_data, encoding.path_data);
+        assert!(culled.draw_tags == encoding.draw_tags);
+        assert_eq!(culled.draw_data, encoding.draw_data);
+        assert_eq!(culled.n_clips, 2);
+        assert_eq!(culled.resources.patches.len(), 4);
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Decoding of the path streams back into geometry.
+
+use peniko::kurbo::{BezPath, PathEl, Point};
+
+use super::{Encoding, PathSegmentType, PathTag, Style, Transform};
+
+impl Encoding {
+    /// Returns an iterator that decodes the encoded paths back into geometry.
+    ///
+    /// One item is produced for each [`PathTag::PATH`], in stream order, so
+    /// the n-th item belongs to the n-th draw object that isn't a glyph run.
+    /// Glyph runs only produce path tags when resolved and are skipped.
+    pub fn decode_paths(&self) -> DecodedPaths<'_> {
+        DecodedPaths {
+            encoding: self,
+            tag_ix: 0,
+            data_ix: 0,
+            n_transforms: 0,
+            n_styles: 0,
+            elements: Vec::new(),
+        }
+    }
+}
+
+/// Iterator over the paths of an [`Encoding`].
+///
+/// Each item holds the path in local coordinates, the transform that maps
+/// it to device space and the style it is drawn with. Stroke cap marker
+/// segments are removed, closed stroke subpaths and all fill subpaths end
+/// with [`PathEl::ClosePath`].
+///
+/// A single path can be encoded with several transforms (see
+/// [`PathEncoder::finish`](crate::PathEncoder::finish)). Such paths are
+/// returned in device space with an identity transform.
+///
+/// Iteration stops early if the path data stream is shorter than the tags
+/// require.
+#[derive(Clone)]
+pub struct DecodedPaths<'a> {
+    encoding: &'a Encoding,
+    tag_ix: usize,
+    data_ix: usize,
+    n_transforms: usize,
+    n_styles: usize,
+    /// Elements of the current path with the index of their transform.
+    elements: Vec<(PathEl, usize)>,
+}
+
+impl DecodedPaths<'_> {
+    fn style(&self) -> Style {
+        self.n_styles
+            .checked_sub(1)
+            .and_then(|ix| self.encoding.styles.get(ix))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn point(&mut self, tag: PathTag) -> Option<Point> {
+        let data = &self.encoding.path_data;
+        if tag.is_f32() {
+            let x = f32::from_bits(*data.get(self.data_ix)?);
+            let y = f32::from_bits(*data.get(self.data_ix + 1)?);
+            self.data_ix += 2;
+            Some(Point::new(x.into(), y.into()))
+        } else {
+            let packed = *data.get(self.data_ix)?;
+            self.data_ix += 1;
+            Some(Point::new(
+                (packed as i16).into(),
+                ((packed >> 16) as i16).into(),
+            ))
+        }
+    }
+
+    /// Decodes a single segment and appends its elements.
+    fn segment(&mut self, tag: PathTag, subpath_start: bool) -> Option<()> {
+        let transform_ix = self.n_transforms;
+        if subpath_start {
+            let p0 = self.point(tag)?;
+            self.elements.push((PathEl::MoveTo(p0), transform_ix));
+        }
+        let el = match tag.path_segment_type() {
+            PathSegmentType::LINE_TO => PathEl::LineTo(self.point(tag)?),
+            PathSegmentType::QUAD_TO => PathEl::QuadTo(self.point(tag)?, self.point(tag)?),
+            _ => PathEl::CurveTo(self.point(tag)?, self.point(tag)?, self.point(tag)?),
+        };
+        if !tag.is_subpath_end() {
+            self.elements.push((el, transform_ix));
+            return Some(());
+        }
+        if self.style().is_fill() {
+            self.elements.push((el, transform_ix));
+            self.elements.push((PathEl::ClosePath, transform_ix));
+        } else if matches!(el, PathEl::LineTo(_)) {
+            // The last segment of a stroked subpath is the cap marker
+            // inserted by the encoder: a line for closed subpaths and a
+            // quad for open ones. It carries no geometry of its own.
+            self.elements.push((PathEl::ClosePath, transform_ix));
+        }
+        Some(())
+    }
+
+    fn take_path(&mut self) -> (BezPath, Transform) {
+        let transforms = &self.encoding.transforms;
+        let transform = |ix: usize| ix.checked_sub(1).and_then(|ix| transforms.get(ix)).copied();
+        let first = self.elements.first().map_or(self.n_transforms, |el| el.1);
+        if self.elements.iter().all(|el| el.1 == first) {
+            let path = self.elements.drain(..).map(|el| el.0).collect();
+            return (path, transform(first).unwrap_or(Transform::IDENTITY));
+        }
+        let path = self
+            .elements
+            .drain(..)
+            .map(|(el, ix)| transform(ix).map_or(el, |t| t.to_kurbo() * el))
+            .collect();
+        (path, Transform::IDENTITY)
+    }
+}
+
+impl Iterator for DecodedPaths<'_> {
+    type Item = (BezPath, Transform, peniko::Style);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut subpath_start = true;
+        while let Some(&tag) = self.encoding.path_tags.get(self.tag_ix) {
+            self.tag_ix += 1;
+            match tag {
+                PathTag::TRANSFORM => self.n_transforms += 1,
+                PathTag::STYLE => self.n_styles += 1,
+                PathTag::PATH => {
+                    let (path, transform) = self.take_path();
+                    return Some((path, transform, self.style().into()));
+                }
+                tag if tag.is_path_segment() => {
+                    self.segment(tag, subpath_start)?;
+                    subpath_start = tag.is_subpath_end();
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::kurbo::{Affine, BezPath, Cap, PathEl, Rect, Shape, Stroke};
+    use peniko::{Fill, Style};
+
+    use crate::{Encoding, PathTag, Transform};
+
+    fn elements(path: &BezPath) -> Vec<PathEl> {
+        path.elements().to_vec()
+    }
+
+    #[test]
+    fn fills() {
+        let mut encoding = Encoding::new();
+        let transform = Transform::from_kurbo(&Affine::scale(2.0));
+        encoding.encode_transform(transform);
+        encoding.encode_fill_style(Fill::EvenOdd);
+        let rect = Rect::new(1.0, 2.0, 3.0, 4.0);
+        encoding.encode_shape(&rect, true);
+        let mut triangle = BezPath::new();
+        triangle.move_to((0.0, 0.0));
+        triangle.quad_to((1.0, 0.0), (1.0, 1.0));
+        triangle.curve_to((0.5, 1.0), (0.0, 0.5), (0.0, 0.25));
+        encoding.encode_path_elements(triangle.iter(), true);
+
+        let paths: Vec<_> = encoding.decode_paths().collect();
+        assert_eq!(paths.len(), 2);
+        let (path, decoded_transform, style) = &paths[0];
+        assert_eq!(*decoded_transform, transform);
+        assert!(matches!(style, Style::Fill(Fill::EvenOdd)));
+        assert_eq!(path.bounding_box(), rect);
+        assert_eq!(path.elements().last(), Some(&PathEl::ClosePath));
+        // The encoder closes fills with an explicit line.
+        triangle.line_to((0.0, 0.0));
+        triangle.close_path();
+        assert_eq!(elements(&paths[1].0), elements(&triangle));
+    }
+
+    #[test]
+    fn stroke_cap_markers() {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        let stroke = Stroke::new(3.0).with_caps(Cap::Round).with_miter_limit(8.0);
+        assert!(encoding.encode_stroke_style(&stroke));
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.line_to((10.0, 0.0));
+        path.quad_to((10.0, 10.0), (0.0, 10.0));
+        path.move_to((20.0, 20.0));
+        path.line_to((30.0, 20.0));
+        path.line_to((30.0, 30.0));
+        path.close_path();
+        encoding.encode_path_elements(path.iter(), false);
+
+        let (decoded, _, style) = encoding.decode_paths().next().unwrap();
+        let Style::Stroke(decoded_stroke) = style else {
+            panic!("expected a stroke style");
+        };
+        assert_eq!(decoded_stroke.width, 3.0);
+        assert_eq!(decoded_stroke.start_cap, Cap::Round);
+        assert_eq!(decoded_stroke.miter_limit, 8.0);
+        let mut expected = elements(&path);
+        expected.insert(6, PathEl::LineTo((20.0, 20.0).into()));
+        assert_eq!(elements(&decoded), expected);
+    }
+
+    #[test]
+    fn i16_segments_and_transforms() {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        let mut encoder = encoding.encode_path(true);
+        encoder.move_to(0.0, 0.0);
+        encoder.line_to(4.0, 0.0);
+        encoder.line_to(4.0, 4.0);
+        encoder.finish(false);
+        encoding.encode_transform(Transform::from_kurbo(&Affine::translate((100.0, 0.0))));
+        // A hand written i16 subpath: (-1, 2) -> (3, -4).
+        encoding.path_data.extend([0x0002_ffff, 0xfffc_0003]);
+        let mut tag = PathTag::LINE_TO_I16;
+        tag.set_subpath_end();
+        encoding.path_tags.extend([tag, PathTag::PATH]);
+
+        let (path, transform, _) = encoding.decode_paths().next().unwrap();
+        // Segments with different transforms are returned in device space.
+        assert_eq!(transform, Transform::IDENTITY);
+        assert_eq!(
+            elements(&path)[5..],
+            [
+                PathEl::MoveTo((99.0, 2.0).into()),
+                PathEl::LineTo((103.0, -4.0).into()),
+                PathEl::ClosePath
+            ]
+        );
+    }
+
+    #[test]
+    fn clip_paths() {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_empty_shape();
+        encoding.encode_begin_clip(peniko::Mix::Normal.into(), 1.0);
+        encoding.encode_end_clip();
+        let paths: Vec<_> = encoding.decode_paths().map(|path| path.0).collect();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].bounding_box(), Rect::ZERO);
+        assert!(paths[1].is_empty());
+    }
+}
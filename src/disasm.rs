@@ -0,0 +1,1141 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Human readable text format for [`Encoding`].
+//!
+//! Each line describes exactly one path tag or draw tag, with the exception
+//! of `move` (the start point of the following subpath, which has no tag of
+//! its own) and `stop` (a color stop belonging to the preceding gradient).
+//! Draw objects are printed directly after the `path` tag that completes
+//! their path. Everything after a `;` is a comment.
+//!
+//! ```text
+//! transform <xx> <yx> <xy> <yy> <dx> <dy>
+//! fill nonzero|evenodd
+//! stroke <width> join=<join> miter=<limit> caps=<start>,<end>
+//! move <x> <y>
+//! line[.i16] <x> <y> [end]
+//! quad[.i16] <x1> <y1> <x2> <y2> [end]
+//! cubic[.i16] <x1> <y1> <x2> <y2> <x3> <y3> [end]
+//! path
+//! color #<rrggbbaa>
+//! linear <x0> <y0> <x1> <y1> extend=<extend>
+//! radial <x0> <y0> <r0> <x1> <y1> <r1> extend=<extend>
+//! sweep <x> <y> <t0> <t1> extend=<extend>
+//! stop <offset> <css color>
+//! image <width>x<height> quality=<quality> extend=<x>,<y> alpha=<alpha> multiplier=<alpha>
+//! blur_rect #<rrggbbaa> <width> <height> <radius> <std_dev>
+//! begin_clip mix=<mix> compose=<compose> alpha=<alpha>
+//! end_clip
+//! glyph_run glyphs=<count> size=<size> hint=<bool>
+//! ```
+//!
+//! Keyed operands must appear in the order shown. Colors of draw objects are
+//! the premultiplied values stored in the draw data stream. Segments marked
+//! `.i16` use the packed 16-bit integer format. Images only record their
+//! metadata and assemble to transparent pixels, and glyph runs can't be
+//! assembled since the text format doesn't carry fonts.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::str::SplitWhitespace;
+
+use peniko::color::{DynamicColor, parse_color};
+use peniko::{Blob, ColorStop, Compose, Extend, Fill, Image, ImageFormat, ImageQuality, Mix};
+
+use super::{
+    DrawBeginClip, DrawBlurRoundedRect, DrawColor, DrawImage, DrawLinearGradient,
+    DrawRadialGradient, DrawSweepGradient, DrawTag, Encoding, Patch, PathSegmentType, PathTag,
+    Style, Transform,
+};
+use crate::math::{f16_to_f32, f32_to_f16};
+
+const FILLS: [(&str, Fill); 2] = [("nonzero", Fill::NonZero), ("evenodd", Fill::EvenOdd)];
+
+const JOINS: [(&str, u32); 3] = [
+    ("bevel", Style::FLAGS_JOIN_BITS_BEVEL),
+    ("miter", Style::FLAGS_JOIN_BITS_MITER),
+    ("round", Style::FLAGS_JOIN_BITS_ROUND),
+];
+
+const CAPS: [(&str, u32); 3] = [
+    ("butt", Style::FLAGS_CAP_BITS_BUTT),
+    ("square", Style::FLAGS_CAP_BITS_SQUARE),
+    ("round", Style::FLAGS_CAP_BITS_ROUND),
+];
+
+const EXTENDS: [(&str, Extend); 3] = [
+    ("pad", Extend::Pad),
+    ("repeat", Extend::Repeat),
+    ("reflect", Extend::Reflect),
+];
+
+const QUALITIES: [(&str, ImageQuality); 3] = [
+    ("low", ImageQuality::Low),
+    ("medium", ImageQuality::Medium),
+    ("high", ImageQuality::High),
+];
+
+const MIXES: [(&str, Mix); 17] = [
+    ("normal", Mix::Normal),
+    ("multiply", Mix::Multiply),
+    ("screen", Mix::Screen),
+    ("overlay", Mix::Overlay),
+    ("darken", Mix::Darken),
+    ("lighten", Mix::Lighten),
+    ("color-dodge", Mix::ColorDodge),
+    ("color-burn", Mix::ColorBurn),
+    ("hard-light", Mix::HardLight),
+    ("soft-light", Mix::SoftLight),
+    ("difference", Mix::Difference),
+    ("exclusion", Mix::Exclusion),
+    ("hue", Mix::Hue),
+    ("saturation", Mix::Saturation),
+    ("color", Mix::Color),
+    ("luminosity", Mix::Luminosity),
+    ("clip", Mix::Clip),
+];
+
+const COMPOSES: [(&str, Compose); 14] = [
+    ("clear", Compose::Clear),
+    ("copy", Compose::Copy),
+    ("dest", Compose::Dest),
+    ("src-over", Compose::SrcOver),
+    ("dest-over", Compose::DestOver),
+    ("src-in", Compose::SrcIn),
+    ("dest-in", Compose::DestIn),
+    ("src-out", Compose::SrcOut),
+    ("dest-out", Compose::DestOut),
+    ("src-atop", Compose::SrcAtop),
+    ("dest-atop", Compose::DestAtop),
+    ("xor", Compose::Xor),
+    ("plus", Compose::Plus),
+    ("plus-lighter", Compose::PlusLighter),
+];
+
+fn name_of<T: PartialEq>(table: &[(&'static str, T)], value: &T) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(_, entry)| entry == value)
+        .map(|(name, _)| *name)
+}
+
+fn lookup<T: Copy>(table: &[(&str, T)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(entry, _)| *entry == name)
+        .map(|(_, value)| *value)
+}
+
+/// Errors that can occur when assembling an [`Encoding`] from text.
+///
+/// Line numbers start at 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssembleError {
+    /// The line starts with an unknown instruction.
+    UnknownInstruction { line: usize, instruction: String },
+    /// An operand is missing.
+    MissingOperand { line: usize },
+    /// An operand couldn't be parsed.
+    InvalidOperand { line: usize, operand: String },
+    /// The line has more operands than the instruction takes.
+    UnexpectedOperand { line: usize, operand: String },
+    /// The instruction isn't valid at this position, for example a segment
+    /// that starts a subpath without a preceding `move`.
+    Misplaced { line: usize },
+    /// The instruction can be disassembled but not assembled.
+    Unsupported { line: usize },
+}
+
+impl AssembleError {
+    /// Returns the line on which the error occurred.
+    pub fn line(&self) -> usize {
+        match self {
+            Self::UnknownInstruction { line, .. }
+            | Self::MissingOperand { line }
+            | Self::InvalidOperand { line, .. }
+            | Self::UnexpectedOperand { line, .. }
+            | Self::Misplaced { line }
+            | Self::Unsupported { line } => *line,
+        }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownInstruction { line, instruction } => {
+                write!(f, "line {line}: unknown instruction `{instruction}`")
+            }
+            Self::MissingOperand { line } => write!(f, "line {line}: missing operand"),
+            Self::InvalidOperand { line, operand } => {
+                write!(f, "line {line}: invalid operand `{operand}`")
+            }
+            Self::UnexpectedOperand { line, operand } => {
+                write!(f, "line {line}: unexpected operand `{operand}`")
+            }
+            Self::Misplaced { line } => {
+                write!(f, "line {line}: instruction is not valid at this position")
+            }
+            Self::Unsupported { line } => {
+                write!(f, "line {line}: instruction can't be assembled")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+impl Encoding {
+    /// Returns a textual disassembly of the encoding.
+    ///
+    /// See [`Encoding::assemble`] for the inverse operation.
+    pub fn disassemble(&self) -> String {
+        let mut text = String::new();
+        self.write_disassembly(&mut text)
+            .expect("writing to a string can't fail");
+        text
+    }
+
+    /// Writes a textual disassembly of the encoding, one line per tag.
+    ///
+    /// Streams that end early are reported with a trailing comment rather
+    /// than a panic, so this can be used on encodings that fail
+    /// [`Encoding::validate`].
+    pub fn write_disassembly(&self, out: &mut impl Write) -> fmt::Result {
+        Disassembler {
+            encoding: self,
+            patches: self
+                .resources
+                .patches
+                .iter()
+                .filter_map(|patch| match patch {
+                    Patch::Ramp {
+                        draw_data_offset, ..
+                    }
+                    | Patch::Image {
+                        draw_data_offset, ..
+                    } => Some((*draw_data_offset, patch)),
+                    Patch::GlyphRun { .. } => None,
+                })
+                .collect(),
+            out,
+            path_data: 0,
+            draw_tags: 0,
+            draw_data: 0,
+        }
+        .run()
+    }
+
+    /// Assembles an encoding from the text produced by
+    /// [`Encoding::disassemble`].
+    ///
+    /// Transforms and styles are added exactly as written, without the
+    /// deduplication performed by [`Encoding::encode_transform`], so
+    /// disassembling the result reproduces the input modulo whitespace and
+    /// comments.
+    pub fn assemble(text: &str) -> Result<Self, AssembleError> {
+        let mut assembler = Assembler::default();
+        for (ix, line) in text.lines().enumerate() {
+            let line_number = ix + 1;
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            assembler.line(line_number, line)?;
+        }
+        assembler.finish(text.lines().count())
+    }
+}
+
+struct Disassembler<'a, W> {
+    encoding: &'a Encoding,
+    patches: HashMap<usize, &'a Patch>,
+    out: &'a mut W,
+    path_data: usize,
+    draw_tags: usize,
+    draw_data: usize,
+}
+
+impl<W: Write> Disassembler<'_, W> {
+    fn run(mut self) -> fmt::Result {
+        let encoding = self.encoding;
+        let mut transforms = encoding.transforms.iter();
+        let mut styles = encoding.styles.iter();
+        let mut glyph_runs = encoding.resources.glyph_runs.iter().peekable();
+        let mut subpath_start = true;
+        for (ix, tag) in encoding.path_tags.iter().enumerate() {
+            // Glyph runs only contribute path tags once resolved, so their
+            // draw objects are printed at the position they will be spliced.
+            while let Some(run) = glyph_runs.next_if(|run| run.stream_offsets.path_tags == ix) {
+                writeln!(
+                    self.out,
+                    "glyph_run glyphs={} size={} hint={}",
+                    run.glyphs.len(),
+                    run.font_size,
+                    run.hint
+                )?;
+                if !self.draw()? {
+                    return Ok(());
+                }
+            }
+            match *tag {
+                PathTag::TRANSFORM => {
+                    let Some(Transform {
+                        matrix: [xx, yx, xy, yy],
+                        translation: [dx, dy],
+                    }) = transforms.next()
+                    else {
+                        return writeln!(self.out, "; transform stream is truncated");
+                    };
+                    writeln!(self.out, "transform {xx} {yx} {xy} {yy} {dx} {dy}")?;
+                }
+                PathTag::STYLE => {
+                    let Some(style) = styles.next() else {
+                        return writeln!(self.out, "; style stream is truncated");
+                    };
+                    self.style(*style)?;
+                }
+                PathTag::PATH => {
+                    writeln!(self.out, "path")?;
+                    if !self.draw()? {
+                        return Ok(());
+                    }
+                }
+                tag if tag.is_path_segment() => {
+                    if !self.segment(tag, subpath_start)? {
+                        return Ok(());
+                    }
+                    subpath_start = tag.is_subpath_end();
+                }
+                tag => writeln!(self.out, "; unknown path tag {:#04x}", tag.0)?,
+            }
+        }
+        for run in glyph_runs {
+            writeln!(
+                self.out,
+                "glyph_run glyphs={} size={} hint={}",
+                run.glyphs.len(),
+                run.font_size,
+                run.hint
+            )?;
+            if !self.draw()? {
+                return Ok(());
+            }
+        }
+        // Draw objects without a path only occur in invalid encodings, but
+        // showing them is more useful than hiding them.
+        while self.draws_remaining() {
+            if !self.draw()? {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    fn draws_remaining(&self) -> bool {
+        self.draw_tags < self.encoding.draw_tags.len()
+    }
+
+    fn style(&mut self, style: Style) -> fmt::Result {
+        let flags = style.flags_and_miter_limit;
+        if flags & Style::FLAGS_STYLE_BIT == 0 {
+            let fill = if flags & Style::FLAGS_FILL_BIT == 0 {
+                "nonzero"
+            } else {
+                "evenodd"
+            };
+            return writeln!(self.out, "fill {fill}");
+        }
+        let join = name_of(&JOINS, &(flags & Style::FLAGS_JOIN_MASK)).unwrap_or("?");
+        let start_cap =
+            name_of(&CAPS, &((flags & Style::FLAGS_START_CAP_MASK) >> 2)).unwrap_or("?");
+        let end_cap = name_of(&CAPS, &(flags & Style::FLAGS_END_CAP_MASK)).unwrap_or("?");
+        let miter_limit = f16_to_f32((flags & Style::MITER_LIMIT_MASK) as u16);
+        writeln!(
+            self.out,
+            "stroke {} join={join} miter={miter_limit} caps={start_cap},{end_cap}",
+            style.line_width
+        )
+    }
+
+    /// Reads a point in the format of `tag`, advancing the data cursor.
+    fn point(&mut self, tag: PathTag) -> Option<Point> {
+        let data = &self.encoding.path_data;
+        if tag.is_f32() {
+            let x = f32::from_bits(*data.get(self.path_data)?);
+            let y = f32::from_bits(*data.get(self.path_data + 1)?);
+            self.path_data += 2;
+            Some(Point::F32(x, y))
+        } else {
+            let packed = *data.get(self.path_data)?;
+            self.path_data += 1;
+            Some(Point::I16(packed as i16, (packed >> 16) as i16))
+        }
+    }
+
+    fn segment(&mut self, tag: PathTag, subpath_start: bool) -> Result<bool, fmt::Error> {
+        if subpath_start {
+            let Some(point) = self.point(tag) else {
+                writeln!(self.out, "; path data is truncated")?;
+                return Ok(false);
+            };
+            writeln!(self.out, "move {point}")?;
+        }
+        let (name, n_points) = match tag.path_segment_type() {
+            PathSegmentType::LINE_TO => ("line", 1),
+            PathSegmentType::QUAD_TO => ("quad", 2),
+            _ => ("cubic", 3),
+        };
+        let mut line = String::from(name);
+        if !tag.is_f32() {
+            line.push_str(".i16");
+        }
+        for _ in 0..n_points {
+            let Some(point) = self.point(tag) else {
+                writeln!(self.out, "; path data is truncated")?;
+                return Ok(false);
+            };
+            write!(line, " {point}")?;
+        }
+        if tag.is_subpath_end() {
+            line.push_str(" end");
+        }
+        writeln!(self.out, "{line}")?;
+        Ok(true)
+    }
+
+    /// Prints the next draw object and its color stops, if any.
+    ///
+    /// Returns `false` if the draw streams ended early.
+    fn draw(&mut self) -> Result<bool, fmt::Error> {
+        let Some(tag) = self.encoding.draw_tags.get(self.draw_tags).copied() else {
+            writeln!(self.out, "; draw tag stream is truncated")?;
+            return Ok(false);
+        };
+        let offset = self.draw_data;
+        let size = tag.scene_size() as usize;
+        let Some(data) = self.encoding.draw_data.get(offset..offset + size) else {
+            writeln!(self.out, "; draw data stream is truncated")?;
+            return Ok(false);
+        };
+        self.draw_tags += 1;
+        self.draw_data += size;
+        let patch = self.patches.get(&offset).copied();
+        match tag {
+            DrawTag::COLOR => {
+                let color: DrawColor = bytemuck::pod_read_unaligned(bytemuck::cast_slice(data));
+                writeln!(self.out, "color {}", Hex(color))?;
+            }
+            DrawTag::LINEAR_GRADIENT => {
+                let DrawLinearGradient {
+                    p0: [x0, y0],
+                    p1: [x1, y1],
+                    ..
+                } = bytemuck::pod_read_unaligned(bytemuck::cast_slice(data));
+                write!(self.out, "linear {x0} {y0} {x1} {y1}")?;
+                self.ramp(patch)?;
+            }
+            DrawTag::RADIAL_GRADIENT => {
+                let DrawRadialGradient {
+                    p0: [x0, y0],
+                    p1: [x1, y1],
+                    r0,
+                    r1,
+                    ..
+                } = bytemuck::pod_read_unaligned(bytemuck::cast_slice(data));
+                write!(self.out, "radial {x0} {y0} {r0} {x1} {y1} {r1}")?;
+                self.ramp(patch)?;
+            }
+            DrawTag::SWEEP_GRADIENT => {
+                let DrawSweepGradient {
+                    p0: [x, y], t0, t1, ..
+                } = bytemuck::pod_read_unaligned(bytemuck::cast_slice(data));
+                write!(self.out, "sweep {x} {y} {t0} {t1}")?;
+                self.ramp(patch)?;
+            }
+            DrawTag::IMAGE => {
+                let DrawImage { width_height, .. } =
+                    bytemuck::pod_read_unaligned(bytemuck::cast_slice(data));
+                write!(
+                    self.out,
+                    "image {}x{}",
+                    width_height >> 16,
+                    width_height & 0xFFFF
+                )?;
+                if let Some(Patch::Image {
+                    image,
+                    alpha_multiplier,
+                    ..
+                }) = patch
+                {
+                    writeln!(
+                        self.out,
+                        " quality={} extend={},{} alpha={} multiplier={alpha_multiplier}",
+                        name_of(&QUALITIES, &image.quality).unwrap_or("?"),
+                        name_of(&EXTENDS, &image.x_extend).unwrap_or("?"),
+                        name_of(&EXTENDS, &image.y_extend).unwrap_or("?"),
+                        image.alpha,
+                    )?;
+                } else {
+                    writeln!(self.out, " ; missing image patch")?;
+                }
+            }
+            DrawTag::BLUR_RECT => {
+                let DrawBlurRoundedRect {
+                    color,
+                    width,
+                    height,
+                    radius,
+                    std_dev,
+                } = bytemuck::pod_read_unaligned(bytemuck::cast_slice(data));
+                writeln!(
+                    self.out,
+                    "blur_rect {} {width} {height} {radius} {std_dev}",
+                    Hex(color)
+                )?;
+            }
+            DrawTag::BEGIN_CLIP => {
+                let DrawBeginClip { blend_mode, alpha } =
+                    bytemuck::pod_read_unaligned(bytemuck::cast_slice(data));
+                let mix = bytemuck::checked::try_cast::<u8, Mix>((blend_mode >> 8) as u8)
+                    .ok()
+                    .and_then(|mix| name_of(&MIXES, &mix));
+                let compose = bytemuck::checked::try_cast::<u8, Compose>(blend_mode as u8)
+                    .ok()
+                    .and_then(|compose| name_of(&COMPOSES, &compose));
+                writeln!(
+                    self.out,
+                    "begin_clip mix={} compose={} alpha={alpha}",
+                    mix.unwrap_or("?"),
+                    compose.unwrap_or("?")
+                )?;
+            }
+            DrawTag::END_CLIP => writeln!(self.out, "end_clip")?,
+            tag => writeln!(self.out, "; unknown draw tag {:#x}", tag.0)?,
+        }
+        Ok(true)
+    }
+
+    /// Finishes a gradient line with its extend mode and color stops.
+    fn ramp(&mut self, patch: Option<&Patch>) -> fmt::Result {
+        let Some(Patch::Ramp { stops, extend, .. }) = patch else {
+            return writeln!(self.out, " ; missing ramp patch");
+        };
+        writeln!(
+            self.out,
+            " extend={}",
+            name_of(&EXTENDS, extend).unwrap_or("?")
+        )?;
+        for stop in self
+            .encoding
+            .resources
+            .color_stops
+            .get(stops.clone())
+            .unwrap_or_default()
+        {
+            writeln!(self.out, "stop {} {}", stop.offset, stop.color)?;
+        }
+        Ok(())
+    }
+}
+
+/// A point read from the path data stream.
+#[derive(Clone, Copy)]
+enum Point {
+    F32(f32, f32),
+    I16(i16, i16),
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::F32(x, y) => write!(f, "{x} {y}"),
+            Self::I16(x, y) => write!(f, "{x} {y}"),
+        }
+    }
+}
+
+/// Formats a draw color as `#rrggbbaa`.
+struct Hex(DrawColor);
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, a] = self.0.rgba.to_le_bytes();
+        write!(f, "#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+}
+
+/// A gradient waiting for its color stops.
+struct PendingGradient {
+    kind: GradientKind,
+    extend: Extend,
+    stops: Vec<ColorStop>,
+}
+
+enum GradientKind {
+    Linear(DrawLinearGradient),
+    Radial(DrawRadialGradient),
+    Sweep(DrawSweepGradient),
+}
+
+#[derive(Default)]
+struct Assembler {
+    encoding: Encoding,
+    /// Start point of the next subpath, with its line number.
+    pending_move: Option<(usize, f32, f32)>,
+    /// True if a subpath has been started and not yet ended.
+    in_subpath: bool,
+    gradient: Option<PendingGradient>,
+}
+
+impl Assembler {
+    fn line(&mut self, line: usize, text: &str) -> Result<(), AssembleError> {
+        let mut tokens = text.split_whitespace();
+        let instruction = tokens.next().unwrap_or_default();
+        let mut ops = Operands { line, tokens };
+        if instruction == "stop" {
+            let Some(gradient) = &mut self.gradient else {
+                return Err(AssembleError::Misplaced { line });
+            };
+            let offset = ops.f32()?;
+            let css = text
+                .trim_start_matches("stop")
+                .trim_start()
+                .trim_start_matches(|c: char| !c.is_whitespace())
+                .trim();
+            let color: DynamicColor =
+                parse_color(css).map_err(|_| AssembleError::InvalidOperand {
+                    line,
+                    operand: css.to_owned(),
+                })?;
+            gradient.stops.push(ColorStop { offset, color });
+            return Ok(());
+        }
+        self.flush_gradient();
+        let (mnemonic, is_i16) = match instruction.strip_suffix(".i16") {
+            Some(mnemonic) => (mnemonic, true),
+            None => (instruction, false),
+        };
+        if is_i16 && !matches!(mnemonic, "line" | "quad" | "cubic") {
+            return Err(AssembleError::UnknownInstruction {
+                line,
+                instruction: instruction.to_owned(),
+            });
+        }
+        if self.pending_move.is_some() && !matches!(mnemonic, "line" | "quad" | "cubic") {
+            return Err(AssembleError::Misplaced { line });
+        }
+        let encoding = &mut self.encoding;
+        match mnemonic {
+            "transform" => {
+                let transform = Transform {
+                    matrix: [ops.f32()?, ops.f32()?, ops.f32()?, ops.f32()?],
+                    translation: [ops.f32()?, ops.f32()?],
+                };
+                ops.finish()?;
+                encoding.path_tags.push(PathTag::TRANSFORM);
+                encoding.transforms.push(transform);
+            }
+            "fill" => {
+                let fill = ops.named(&FILLS)?;
+                ops.finish()?;
+                encoding.path_tags.push(PathTag::STYLE);
+                encoding.styles.push(Style::from_fill(fill));
+            }
+            "stroke" => {
+                let line_width = ops.f32()?;
+                let join = ops.keyed("join", |value| lookup(&JOINS, value))?;
+                let miter_limit = ops.keyed("miter", |value| value.parse::<f32>().ok())?;
+                let (start_cap, end_cap) = ops.keyed("caps", |value| {
+                    let (start, end) = value.split_once(',')?;
+                    Some((lookup(&CAPS, start)?, lookup(&CAPS, end)?))
+                })?;
+                ops.finish()?;
+                encoding.path_tags.push(PathTag::STYLE);
+                encoding.styles.push(Style {
+                    flags_and_miter_limit: Style::FLAGS_STYLE_BIT
+                        | join
+                        | (start_cap << 2)
+                        | end_cap
+                        | f32_to_f16(miter_limit) as u32,
+                    line_width,
+                });
+            }
+            "move" => {
+                if self.in_subpath {
+                    return Err(AssembleError::Misplaced { line });
+                }
+                let (x, y) = (ops.f32()?, ops.f32()?);
+                ops.finish()?;
+                self.pending_move = Some((line, x, y));
+            }
+            "line" | "quad" | "cubic" => {
+                let (mut tag, n_points) = match (mnemonic, is_i16) {
+                    ("line", false) => (PathTag::LINE_TO_F32, 1),
+                    ("line", true) => (PathTag::LINE_TO_I16, 1),
+                    ("quad", false) => (PathTag::QUAD_TO_F32, 2),
+                    ("quad", true) => (PathTag::QUAD_TO_I16, 2),
+                    (_, false) => (PathTag::CUBIC_TO_F32, 3),
+                    (_, true) => (PathTag::CUBIC_TO_I16, 3),
+                };
+                if let Some((move_line, x, y)) = self.pending_move.take() {
+                    push_point(&mut encoding.path_data, is_i16, move_line, x, y)?;
+                    self.in_subpath = true;
+                } else if !self.in_subpath {
+                    return Err(AssembleError::Misplaced { line });
+                }
+                for _ in 0..n_points {
+                    let (x, y) = (ops.f32()?, ops.f32()?);
+                    push_point(&mut encoding.path_data, is_i16, line, x, y)?;
+                }
+                match ops.tokens.next() {
+                    Some("end") => {
+                        tag.set_subpath_end();
+                        self.in_subpath = false;
+                    }
+                    Some(operand) => {
+                        return Err(AssembleError::UnexpectedOperand {
+                            line,
+                            operand: operand.to_owned(),
+                        });
+                    }
+                    None => {}
+                }
+                ops.finish()?;
+                encoding.path_tags.push(tag);
+                encoding.n_path_segments += 1;
+            }
+            "path" => {
+                ops.finish()?;
+                encoding.path_tags.push(PathTag::PATH);
+                encoding.n_paths += 1;
+            }
+            "color" => {
+                let color = ops.color()?;
+                ops.finish()?;
+                encoding.encode_color(color);
+            }
+            "linear" | "radial" | "sweep" => {
+                let kind = match mnemonic {
+                    "linear" => GradientKind::Linear(DrawLinearGradient {
+                        index: 0,
+                        p0: [ops.f32()?, ops.f32()?],
+                        p1: [ops.f32()?, ops.f32()?],
+                    }),
+                    "radial" => {
+                        let (p0, r0) = ([ops.f32()?, ops.f32()?], ops.f32()?);
+                        let (p1, r1) = ([ops.f32()?, ops.f32()?], ops.f32()?);
+                        GradientKind::Radial(DrawRadialGradient {
+                            index: 0,
+                            p0,
+                            p1,
+                            r0,
+                            r1,
+                        })
+                    }
+                    _ => GradientKind::Sweep(DrawSweepGradient {
+                        index: 0,
+                        p0: [ops.f32()?, ops.f32()?],
+                        t0: ops.f32()?,
+                        t1: ops.f32()?,
+                    }),
+                };
+                let extend = ops.keyed("extend", |value| lookup(&EXTENDS, value))?;
+                ops.finish()?;
+                self.gradient = Some(PendingGradient {
+                    kind,
+                    extend,
+                    stops: Vec::new(),
+                });
+            }
+            "image" => {
+                let (width, height) = ops.parse(|value| {
+                    let (width, height) = value.split_once('x')?;
+                    Some((width.parse::<u32>().ok()?, height.parse::<u32>().ok()?))
+                })?;
+                let quality = ops.keyed("quality", |value| lookup(&QUALITIES, value))?;
+                let (x_extend, y_extend) = ops.keyed("extend", |value| {
+                    let (x, y) = value.split_once(',')?;
+                    Some((lookup(&EXTENDS, x)?, lookup(&EXTENDS, y)?))
+                })?;
+                let alpha = ops.keyed("alpha", |value| value.parse::<f32>().ok())?;
+                let multiplier = ops.keyed("multiplier", |value| value.parse::<f32>().ok())?;
+                ops.finish()?;
+                let pixels = vec![0_u8; width as usize * height as usize * 4];
+                let mut image = Image::new(Blob::from(pixels), ImageFormat::Rgba8, width, height)
+                    .with_x_extend(x_extend)
+                    .with_y_extend(y_extend)
+                    .with_quality(quality);
+                image.alpha = alpha;
+                encoding.encode_image(&image, multiplier);
+            }
+            "blur_rect" => {
+                let color = ops.color()?;
+                let (width, height) = (ops.f32()?, ops.f32()?);
+                let (radius, std_dev) = (ops.f32()?, ops.f32()?);
+                ops.finish()?;
+                encoding.encode_blurred_rounded_rect(color, width, height, radius, std_dev);
+            }
+            "begin_clip" => {
+                let mix = ops.keyed("mix", |value| lookup(&MIXES, value))?;
+                let compose = ops.keyed("compose", |value| lookup(&COMPOSES, value))?;
+                let alpha = ops.keyed("alpha", |value| value.parse::<f32>().ok())?;
+                ops.finish()?;
+                encoding.encode_begin_clip(peniko::BlendMode { mix, compose }, alpha);
+            }
+            "end_clip" => {
+                ops.finish()?;
+                if encoding.n_open_clips == 0 {
+                    return Err(AssembleError::Misplaced { line });
+                }
+                // The dummy path of the clip is written as a separate `path`
+                // line, so only the draw tag is added here.
+                encoding.draw_tags.push(DrawTag::END_CLIP);
+                encoding.n_clips += 1;
+                encoding.n_open_clips -= 1;
+            }
+            "glyph_run" => return Err(AssembleError::Unsupported { line }),
+            _ => {
+                return Err(AssembleError::UnknownInstruction {
+                    line,
+                    instruction: instruction.to_owned(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn flush_gradient(&mut self) {
+        let Some(PendingGradient {
+            kind,
+            extend,
+            stops,
+        }) = self.gradient.take()
+        else {
+            return;
+        };
+        let stops = stops.into_iter();
+        match kind {
+            GradientKind::Linear(gradient) => {
+                self.encoding
+                    .encode_linear_gradient(gradient, stops, 1.0, extend);
+            }
+            GradientKind::Radial(gradient) => {
+                self.encoding
+                    .encode_radial_gradient(gradient, stops, 1.0, extend);
+            }
+            GradientKind::Sweep(gradient) => {
+                self.encoding
+                    .encode_sweep_gradient(gradient, stops, 1.0, extend);
+            }
+        }
+    }
+
+    fn finish(mut self, n_lines: usize) -> Result<Encoding, AssembleError> {
+        self.flush_gradient();
+        if let Some((line, ..)) = self.pending_move {
+            return Err(AssembleError::Misplaced { line });
+        }
+        if self.in_subpath {
+            return Err(AssembleError::Misplaced { line: n_lines });
+        }
+        Ok(self.encoding)
+    }
+}
+
+fn push_point(
+    data: &mut Vec<u32>,
+    is_i16: bool,
+    line: usize,
+    x: f32,
+    y: f32,
+) -> Result<(), AssembleError> {
+    if !is_i16 {
+        data.extend([x.to_bits(), y.to_bits()]);
+        return Ok(());
+    }
+    let to_i16 = |value: f32| {
+        let int = value as i16;
+        if int as f32 == value {
+            Ok(int as u16 as u32)
+        } else {
+            Err(AssembleError::InvalidOperand {
+                line,
+                operand: value.to_string(),
+            })
+        }
+    };
+    data.push(to_i16(x)? | (to_i16(y)? << 16));
+    Ok(())
+}
+
+/// Operands of a single instruction.
+struct Operands<'a> {
+    line: usize,
+    tokens: SplitWhitespace<'a>,
+}
+
+impl Operands<'_> {
+    fn parse<T>(&mut self, parse: impl FnOnce(&str) -> Option<T>) -> Result<T, AssembleError> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or(AssembleError::MissingOperand { line: self.line })?;
+        parse(token).ok_or_else(|| AssembleError::InvalidOperand {
+            line: self.line,
+            operand: token.to_owned(),
+        })
+    }
+
+    fn f32(&mut self) -> Result<f32, AssembleError> {
+        self.parse(|token| token.parse().ok())
+    }
+
+    fn named<T: Copy>(&mut self, table: &[(&str, T)]) -> Result<T, AssembleError> {
+        self.parse(|token| lookup(table, token))
+    }
+
+    fn color(&mut self) -> Result<DrawColor, AssembleError> {
+        self.parse(|token| {
+            let hex = token.strip_prefix('#').filter(|hex| hex.len() == 8)?;
+            let rgba = u32::from_str_radix(hex, 16).ok()?;
+            Some(DrawColor {
+                rgba: u32::from_le_bytes(rgba.to_be_bytes()),
+            })
+        })
+    }
+
+    /// Parses a `key=value` operand.
+    fn keyed<T>(
+        &mut self,
+        key: &str,
+        parse: impl FnOnce(&str) -> Option<T>,
+    ) -> Result<T, AssembleError> {
+        self.parse(|token| {
+            let value = token.strip_prefix(key)?.strip_prefix('=')?;
+            parse(value)
+        })
+    }
+
+    fn finish(mut self) -> Result<(), AssembleError> {
+        match self.tokens.next() {
+            Some(operand) => Err(AssembleError::UnexpectedOperand {
+                line: self.line,
+                operand: operand.to_owned(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::color::palette;
+    use peniko::kurbo::{Affine, BezPath, Cap, Join, Rect, Stroke};
+    use peniko::{
+        BlendMode, Blob, Color, ColorStop, Compose, Extend, Fill, Image, ImageFormat, ImageQuality,
+        Mix,
+    };
+
+    use super::{AssembleError, Encoding, PathTag};
+    use crate::{DrawLinearGradient, DrawRadialGradient, DrawSweepGradient, Transform};
+
+    fn test_encoding() -> Encoding {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::from_kurbo(&Affine::translate((10.0, -2.5))));
+        encoding.encode_fill_style(Fill::EvenOdd);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 4.0, 4.0), true);
+        encoding.encode_color(Color::from_rgba8(0x00, 0xca, 0xfe, 0x80));
+        let stroke = Stroke::new(1.5)
+            .with_join(Join::Round)
+            .with_caps(Cap::Square)
+            .with_miter_limit(2.5);
+        assert!(encoding.encode_stroke_style(&stroke));
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.quad_to((1.0, 2.0), (3.0, 0.25));
+        path.curve_to((4.0, 5.0), (6.0, 7.0), (8.0, 0.1));
+        encoding.encode_path_elements(path.into_iter(), false);
+        encoding.encode_begin_clip(BlendMode::new(Mix::Multiply, Compose::SrcOver), 0.5);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&Rect::new(1.0, 1.0, 2.0, 2.0), true);
+        encoding.encode_linear_gradient(
+            DrawLinearGradient {
+                index: 0,
+                p0: [0.0, 0.0],
+                p1: [1.0, 1.0],
+            },
+            [
+                ColorStop::from((0.0, palette::css::RED)),
+                ColorStop::from((1.0, Color::new([0.0, 0.0, 1.0, 0.5]))),
+            ]
+            .into_iter(),
+            1.0,
+            Extend::Reflect,
+        );
+        encoding.encode_shape(&Rect::new(2.0, 2.0, 3.0, 3.0), true);
+        encoding.encode_sweep_gradient(
+            DrawSweepGradient {
+                index: 0,
+                p0: [5.0, 5.0],
+                t0: 0.0,
+                t1: 0.75,
+            },
+            [
+                ColorStop::from((0.0, palette::css::LIME)),
+                ColorStop::from((1.0, palette::css::BLUE)),
+            ]
+            .into_iter(),
+            1.0,
+            Extend::Pad,
+        );
+        encoding.encode_shape(&Rect::new(3.0, 3.0, 4.0, 4.0), true);
+        encoding.encode_radial_gradient(
+            DrawRadialGradient {
+                index: 0,
+                p0: [0.0, 0.0],
+                p1: [1.0, 0.0],
+                r0: 0.0,
+                r1: 4.0,
+            },
+            [
+                ColorStop::from((0.25, palette::css::WHITE)),
+                ColorStop::from((0.75, palette::css::TRANSPARENT)),
+            ]
+            .into_iter(),
+            1.0,
+            Extend::Repeat,
+        );
+        encoding.encode_end_clip();
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 2.0, 2.0), true);
+        let image = Image::new(Blob::from(vec![0xff_u8; 16]), ImageFormat::Rgba8, 2, 2)
+            .with_extend(Extend::Reflect)
+            .with_quality(ImageQuality::High);
+        encoding.encode_image(&image, 0.5);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 10.0, 20.0), true);
+        encoding.encode_blurred_rounded_rect(palette::css::BLACK, 10.0, 20.0, 2.0, 1.0);
+        encoding
+    }
+
+    fn assert_streams_eq(a: &Encoding, b: &Encoding) {
+        assert!(a.path_tags == b.path_tags);
+        assert_eq!(a.path_data, b.path_data);
+        assert!(a.draw_tags == b.draw_tags);
+        assert_eq!(a.draw_data, b.draw_data);
+        assert_eq!(a.transforms, b.transforms);
+        assert_eq!(a.styles, b.styles);
+        assert_eq!(
+            (a.n_paths, a.n_path_segments, a.n_clips, a.n_open_clips),
+            (b.n_paths, b.n_path_segments, b.n_clips, b.n_open_clips)
+        );
+        assert_eq!(a.resources.color_stops, b.resources.color_stops);
+        assert_eq!(a.resources.patches.len(), b.resources.patches.len());
+    }
+
+    #[test]
+    fn round_trip() {
+        let encoding = test_encoding();
+        let text = encoding.disassemble();
+        let assembled = Encoding::assemble(&text).ok().unwrap();
+        assert_streams_eq(&assembled, &encoding);
+        assert_eq!(assembled.disassemble(), text);
+    }
+
+    #[test]
+    fn hand_authored() {
+        let text = "
+            ; a square with an i16 triangle next to it
+            transform 1 0 0 1 0 0
+            fill nonzero
+            move 0 0
+            line 4 0
+            line 4 4
+            line 0 4
+            line 0 0 end
+            move 10 0
+            line.i16 -12 0
+            line.i16 11 5 end
+            path
+            color #ff0000ff
+            stroke 2 join=miter miter=4 caps=butt,round
+            path
+            begin_clip mix=screen compose=src-over alpha=1
+        ";
+        let Ok(encoding) = Encoding::assemble(text) else {
+            panic!("failed to assemble");
+        };
+        assert_eq!(encoding.n_paths, 2);
+        assert_eq!(encoding.n_path_segments, 6);
+        assert_eq!(encoding.n_open_clips, 1);
+        assert!(encoding.path_tags[7] == PathTag(PathTag::LINE_TO_I16.0 | 0x4));
+        assert_eq!(encoding.path_data[10..], [10, 0xfff4, 11 | 5 << 16]);
+        assert_eq!(encoding.draw_data[0], 0xff00_00ff);
+        assert!(encoding.validate().is_ok());
+
+        let mut expected = Encoding::new();
+        expected.encode_transform(Transform::IDENTITY);
+        expected.encode_fill_style(Fill::NonZero);
+        expected.encode_shape(&Rect::new(0.0, 0.0, 4.0, 4.0), true);
+        assert_eq!(encoding.path_data[..10], expected.path_data[..]);
+        let text = encoding.disassemble();
+        assert!(text.contains("line.i16 -12 0\n"));
+        assert!(text.contains("stroke 2 join=miter miter=4 caps=butt,round\n"));
+    }
+
+    #[test]
+    fn errors() {
+        let error = |text: &str| Encoding::assemble(text).err().unwrap();
+        assert_eq!(
+            error("path\nfrob 1"),
+            AssembleError::UnknownInstruction {
+                line: 2,
+                instruction: "frob".into()
+            }
+        );
+        assert_eq!(error("line 1 1"), AssembleError::Misplaced { line: 1 });
+        assert_eq!(
+            error("move 0 0\npath"),
+            AssembleError::Misplaced { line: 2 }
+        );
+        assert_eq!(
+            error("move 0 0\nline 1 1"),
+            AssembleError::Misplaced { line: 2 }
+        );
+        assert_eq!(
+            error("move 0 0\nline.i16 0.5 1 end"),
+            AssembleError::InvalidOperand {
+                line: 2,
+                operand: "0.5".into()
+            }
+        );
+        assert_eq!(
+            error("color #ff"),
+            AssembleError::InvalidOperand {
+                line: 1,
+                operand: "#ff".into()
+            }
+        );
+        assert_eq!(
+            error("path 1"),
+            AssembleError::UnexpectedOperand {
+                line: 1,
+                operand: "1".into()
+            }
+        );
+        assert_eq!(error("fill"), AssembleError::MissingOperand { line: 1 });
+        assert_eq!(error("stop 0 red"), AssembleError::Misplaced { line: 1 });
+        assert_eq!(
+            error("glyph_run glyphs=1 size=12 hint=false"),
+            AssembleError::Unsupported { line: 1 }
+        );
+    }
+
+    #[test]
+    fn truncated_streams() {
+        let mut encoding = test_encoding();
+        encoding.path_data.truncate(5);
+        let text = encoding.disassemble();
+        assert!(text.ends_with("; path data is truncated\n"));
+    }
+}
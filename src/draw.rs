@@ -5,6 +5,7 @@ use bytemuck::{Pod, Zeroable};
 use peniko::{
     BlendMode,
     color::{AlphaColor, ColorSpace, DynamicColor, OpaqueColor, PremulColor, Srgb},
+    kurbo::Rect,
 };
 
 use super::Monoid;
@@ -48,6 +49,11 @@ impl DrawTag {
     pub const fn info_size(self) -> u32 {
         (self.0 >> 6) & 0xf
     }
+
+    /// Returns the size of the draw data (in u32s) used by this tag.
+    pub const fn scene_size(self) -> u32 {
+        (self.0 >> 2) & 0x7
+    }
 }
 
 /// The first word of each draw info stream entry contains the flags.
@@ -64,6 +70,14 @@ pub struct DrawBbox {
     pub bbox: [f32; 4],
 }
 
+impl From<Rect> for DrawBbox {
+    fn from(rect: Rect) -> Self {
+        Self {
+            bbox: [rect.x0, rect.y0, rect.x1, rect.y1].map(|x| x as f32),
+        }
+    }
+}
+
 /// Draw data for a solid color.
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
 #[repr(C)]
@@ -164,8 +178,11 @@ pub struct DrawImage {
     pub xy: u32,
     /// Packed image dimensions.
     pub width_height: u32,
-    /// Packed quality, extend mode and 8-bit alpha (bits `qqxxyyaaaaaaaa`,
-    /// 18 unused prefix bits).
+    /// Packed atlas page, quality, extend mode and 8-bit alpha (bits
+    /// `pppppppppppppppp00qqxxyyaaaaaaaa`).
+    ///
+    /// The page index is the layer of the atlas texture array and is set
+    /// when the encoding is resolved.
     pub sample_alpha: u32,
 }
 
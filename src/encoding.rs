@@ -45,6 +45,12 @@ pub struct Encoding {
     pub n_open_clips: u32,
     /// Flags that capture the current state of the encoding.
     pub flags: u32,
+    /// Tolerance for the compact `i16` path segment encoding, or `None` to
+    /// always encode `f32` segments.
+    ///
+    /// This is configuration rather than state, so it is preserved by
+    /// [`Encoding::reset`]. See [`PathEncoder::with_i16_tolerance`].
+    pub i16_tolerance: Option<f32>,
 }
 
 impl Encoding {
@@ -159,6 +165,8 @@ impl Encoding {
         if let Some(transform) = *transform {
             self.transforms
                 .extend(other.transforms.iter().map(|x| transform * *x));
+            // Brush transforms are part of the transform stream, except for
+            // glyph runs where they are relative to the run transform.
             for run in &mut self.resources.glyph_runs[glyph_runs_base..] {
                 run.transform = transform * run.transform;
             }
@@ -199,7 +207,7 @@ impl Encoding {
         }
     }
 
-    fn encode_style(&mut self, style: Style) {
+    pub(crate) fn encode_style(&mut self, style: Style) {
         if self.flags & Self::FORCE_NEXT_STYLE != 0 || self.styles.last() != Some(&style) {
             self.path_tags.push(PathTag::STYLE);
             self.styles.push(style);
@@ -234,6 +242,7 @@ impl Encoding {
             &mut self.n_paths,
             is_fill,
         )
+        .with_i16_tolerance(self.i16_tolerance)
     }
 
     /// Encodes a shape. If `is_fill` is true, all subpaths will be automatically closed.
@@ -491,8 +500,60 @@ impl Encoding {
         self.flags |= Self::FORCE_NEXT_TRANSFORM | Self::FORCE_NEXT_STYLE;
     }
 
-    // Swap the last two tags in the path tag stream; used for transformed
-    // gradients.
+    /// Encodes the transform of the brush of the path that was just encoded.
+    ///
+    /// `brush_transform` maps brush space (the coordinates of gradients,
+    /// images and blurred rectangles) to the local space of the path and is
+    /// combined with the current transform. This must be called after the
+    /// path is encoded and before the brush, which picks up the transform
+    /// while the path segments keep using the path transform.
+    ///
+    /// The brush transform becomes the current transform, so the next path
+    /// has to encode its transform again. Returns false if the combined
+    /// transform matches the path transform, in which case nothing is
+    /// encoded.
+    pub fn encode_brush_transform(&mut self, brush_transform: Transform) -> bool {
+        debug_assert!(
+            self.path_tags.last() == Some(&PathTag::PATH),
+            "a brush transform must directly follow the path"
+        );
+        let transform = self
+            .transforms
+            .last()
+            .copied()
+            .unwrap_or(Transform::IDENTITY);
+        if self.encode_transform(transform * brush_transform) {
+            // The draw object uses the transform that is current at its path
+            // marker.
+            self.swap_last_path_tags();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Encodes a brush with an optional alpha modifier and a brush transform.
+    ///
+    /// See [`Encoding::encode_brush_transform`] for the meaning of
+    /// `brush_transform`.
+    #[expect(
+        single_use_lifetimes,
+        reason = "False positive: https://github.com/rust-lang/rust/issues/129255"
+    )]
+    pub fn encode_brush_with_transform<'b>(
+        &mut self,
+        brush: impl Into<BrushRef<'b>>,
+        alpha: f32,
+        brush_transform: Transform,
+    ) {
+        self.encode_brush_transform(brush_transform);
+        self.encode_brush(brush, alpha);
+    }
+
+    /// Swaps the last two tags in the path tag stream.
+    ///
+    /// This is the low level operation behind
+    /// [`Encoding::encode_brush_transform`], which should be preferred.
     pub fn swap_last_path_tags(&mut self) {
         let len = self.path_tags.len();
         self.path_tags.swap(len - 1, len - 2);
@@ -597,7 +658,11 @@ impl StreamOffsets {
 
 #[cfg(test)]
 mod tests {
-    use peniko::{Extend, ImageQuality};
+    use peniko::kurbo::{Affine, Rect};
+    use peniko::{Color, ColorStop, Extend, Fill, Gradient, ImageQuality};
+
+    use super::Encoding;
+    use crate::{PathTag, Transform};
 
     #[test]
     fn ensure_image_quality_values() {
@@ -620,4 +685,48 @@ mod tests {
             Extend::Pad | Extend::Repeat | Extend::Reflect => {}
         }
     }
+
+    #[test]
+    fn brush_transform() {
+        let transform = Transform::from_kurbo(&Affine::translate((10.0, 0.0)));
+        let brush_transform = Transform::from_kurbo(&Affine::scale(4.0));
+        let gradient = Gradient::new_linear((0.0, 0.0), (1.0, 0.0)).with_stops(
+            [
+                ColorStop::from((0.0, Color::BLACK)),
+                ColorStop::from((1.0, Color::WHITE)),
+            ]
+            .as_slice(),
+        );
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(transform);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 1.0, 1.0), true);
+        encoding.encode_brush_with_transform(&gradient, 1.0, brush_transform);
+        // An identity brush transform encodes nothing.
+        encoding.encode_transform(transform);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 1.0, 1.0), true);
+        assert!(!encoding.encode_brush_transform(Transform::IDENTITY));
+        encoding.encode_color(Color::WHITE);
+        assert!(encoding.validate().is_ok());
+        assert_eq!(
+            encoding.transforms,
+            [transform, transform * brush_transform, transform]
+        );
+        let path_ix = encoding
+            .path_tags
+            .iter()
+            .position(|tag| *tag == PathTag::PATH);
+        assert!(encoding.path_tags[path_ix.unwrap() - 1] == PathTag::TRANSFORM);
+        // The path keeps its own transform.
+        let (_, decoded_transform, _) = encoding.decode_paths().next().unwrap();
+        assert_eq!(decoded_transform, transform);
+
+        let offset = Transform::from_kurbo(&Affine::translate((0.0, 5.0)));
+        let mut appended = Encoding::new();
+        appended.append(&encoding, &Some(offset));
+        assert_eq!(
+            appended.transforms[..2],
+            [offset * transform, offset * transform * brush_transform]
+        );
+    }
 }
@@ -3,9 +3,11 @@
 
 use std::ops::Range;
 
-use peniko::{Font, Style};
+use peniko::color::palette;
+use peniko::{BrushRef, Fill, Font, Style, StyleRef};
 
-use super::{StreamOffsets, Transform};
+use super::{Encoding, NormalizedCoord, Patch, StreamOffsets, Transform};
+use crate::color_glyph::ColorFace;
 
 /// Positioned glyph.
 #[derive(Copy, Clone, Default, Debug)]
@@ -27,10 +29,20 @@ pub struct GlyphRun {
     pub transform: Transform,
     /// Per-glyph transform.
     pub glyph_transform: Option<Transform>,
+    /// Transform of the brush relative to the run transform.
+    ///
+    /// Without it, the brush uses the transform of the last glyph.
+    pub brush_transform: Option<Transform>,
     /// Size of the font in pixels per em.
     pub font_size: f32,
     /// True if hinting is enabled.
     pub hint: bool,
+    /// Distance in pixels by which outlines are expanded to synthesize a
+    /// bold face.
+    pub embolden: f32,
+    /// Angle in radians by which glyphs are slanted to the right to
+    /// synthesize an oblique face.
+    pub skew: f32,
     /// Range of normalized coordinates in the parent encoding.
     pub normalized_coords: Range<usize>,
     /// Fill or stroke style.
@@ -43,3 +55,288 @@ pub struct GlyphRun {
     /// This contains the shaped text with accurate glyph positions and metrics.
     pub buffer: Option<std::sync::Arc<glyphon::Buffer>>,
 }
+
+impl GlyphRun {
+    /// Returns the per-glyph transform combined with the shear of the
+    /// synthetic oblique, which is applied first.
+    pub(crate) fn effective_glyph_transform(&self) -> Option<Transform> {
+        if self.skew == 0.0 {
+            return self.glyph_transform;
+        }
+        let shear = Transform {
+            matrix: [1.0, 0.0, self.skew.tan(), 1.0],
+            translation: [0.0, 0.0],
+        };
+        Some(self.glyph_transform.map_or(shear, |xform| xform * shear))
+    }
+}
+
+impl Encoding {
+    /// Returns a builder that encodes a run of glyphs drawn with `brush`.
+    ///
+    /// The glyphs are expanded into paths by the [`Resolver`](crate::Resolver)
+    /// and nothing is encoded until [`GlyphRunBuilder::glyphs`] is called.
+    #[expect(
+        clippy::too_many_arguments,
+        reason = "Mirrors the properties of a glyph run"
+    )]
+    #[expect(
+        single_use_lifetimes,
+        reason = "False positive: https://github.com/rust-lang/rust/issues/129255"
+    )]
+    pub fn encode_glyph_run<'a, 'b>(
+        &'a mut self,
+        font: &Font,
+        font_size: f32,
+        transform: Transform,
+        glyph_transform: Option<Transform>,
+        style: impl Into<StyleRef<'b>>,
+        brush: impl Into<BrushRef<'a>>,
+        hint: bool,
+        normalized_coords: &[NormalizedCoord],
+    ) -> GlyphRunBuilder<'a> {
+        let mut builder = GlyphRunBuilder::new(self, font);
+        builder.run.font_size = font_size;
+        builder.run.transform = transform;
+        builder.run.glyph_transform = glyph_transform;
+        builder.run.style = style.into().to_owned();
+        builder.run.hint = hint;
+        builder.brush = brush.into();
+        builder.set_normalized_coords(normalized_coords);
+        builder
+    }
+}
+
+/// Builder for a run of glyphs, returned by [`Encoding::encode_glyph_run`].
+#[must_use = "the glyph run is only encoded by `glyphs`"]
+pub struct GlyphRunBuilder<'a> {
+    encoding: &'a mut Encoding,
+    pub(crate) run: GlyphRun,
+    pub(crate) brush: BrushRef<'a>,
+    pub(crate) brush_alpha: f32,
+}
+
+impl<'a> GlyphRunBuilder<'a> {
+    /// Creates a builder for a run of 16 pixel glyphs filled with solid
+    /// black.
+    pub(crate) fn new(encoding: &'a mut Encoding, font: &Font) -> Self {
+        let coords_start = encoding.resources.normalized_coords.len();
+        let glyphs_start = encoding.resources.glyphs.len();
+        let stream_offsets = encoding.stream_offsets();
+        Self {
+            encoding,
+            run: GlyphRun {
+                font: font.clone(),
+                transform: Transform::IDENTITY,
+                glyph_transform: None,
+                brush_transform: None,
+                font_size: 16.0,
+                hint: false,
+                embolden: 0.0,
+                skew: 0.0,
+                normalized_coords: coords_start..coords_start,
+                style: Fill::NonZero.into(),
+                glyphs: glyphs_start..glyphs_start,
+                stream_offsets,
+                buffer: None,
+            },
+            brush: palette::css::BLACK.into(),
+            brush_alpha: 1.0,
+        }
+    }
+
+    /// Replaces the normalized coordinates of the run.
+    pub(crate) fn set_normalized_coords(&mut self, coords: &[NormalizedCoord]) {
+        let resources = &mut self.encoding.resources;
+        resources
+            .normalized_coords
+            .truncate(self.run.normalized_coords.start);
+        resources.normalized_coords.extend_from_slice(coords);
+        self.run.normalized_coords.end = resources.normalized_coords.len();
+    }
+
+    /// Sets the brush transform, relative to the run transform.
+    ///
+    /// The default value is `None`, which leaves the brush in the coordinate
+    /// space of the last glyph.
+    pub fn brush_transform(mut self, transform: Option<Transform>) -> Self {
+        self.run.brush_transform = transform;
+        self
+    }
+
+    /// Sets an additional alpha multiplier for the brush.
+    ///
+    /// The default value is 1.0.
+    pub fn brush_alpha(mut self, alpha: f32) -> Self {
+        self.brush_alpha = alpha;
+        self
+    }
+
+    /// Sets the distance in pixels by which outlines are expanded to
+    /// synthesize a bold face. Color glyphs aren't emboldened.
+    ///
+    /// The default value is 0.0.
+    pub fn embolden(mut self, embolden: f32) -> Self {
+        self.run.embolden = embolden;
+        self
+    }
+
+    /// Sets the angle in radians by which glyphs are slanted to the right to
+    /// synthesize an oblique face.
+    ///
+    /// The shear is applied before the glyph transform. The default value is
+    /// 0.0.
+    pub fn skew(mut self, skew: f32) -> Self {
+        self.run.skew = skew;
+        self
+    }
+
+    /// Encodes the run for the given glyphs and consumes the builder.
+    ///
+    /// Color glyphs are encoded in place as separate draw objects, which
+    /// splits the run around them. An empty sequence of glyphs encodes
+    /// nothing.
+    pub fn glyphs(self, glyphs: impl IntoIterator<Item = Glyph>) {
+        let Self {
+            encoding,
+            run,
+            brush,
+            brush_alpha,
+        } = self;
+        let font = run.font.clone();
+        let coords = &encoding.resources.normalized_coords[run.normalized_coords.clone()];
+        let color_face = ColorFace::new(&font, coords, run.font_size);
+        let mut glyphs = glyphs.into_iter();
+        let mut n_runs = 0;
+        loop {
+            let start = encoding.resources.glyphs.len();
+            let mut color_glyph = None;
+            for glyph in glyphs.by_ref() {
+                if color_face
+                    .as_ref()
+                    .is_some_and(|face| face.is_color_glyph(glyph.id))
+                {
+                    color_glyph = Some(glyph);
+                    break;
+                }
+                encoding.resources.glyphs.push(glyph);
+            }
+            let end = encoding.resources.glyphs.len();
+            if end > start {
+                let run = GlyphRun {
+                    glyphs: start..end,
+                    stream_offsets: encoding.stream_offsets(),
+                    ..run.clone()
+                };
+                let index = encoding.resources.glyph_runs.len();
+                encoding.resources.glyph_runs.push(run);
+                encoding.resources.patches.push(Patch::GlyphRun { index });
+                encoding.encode_brush(brush, brush_alpha);
+                // The glyph run is expanded into transforms, styles and paths
+                // at resolve time, which invalidates the current state.
+                encoding.force_next_transform_and_style();
+                n_runs += 1;
+            }
+            let (Some(glyph), Some(face)) = (color_glyph, &color_face) else {
+                break;
+            };
+            face.encode(encoding, &run, &glyph, brush, brush_alpha);
+        }
+        if n_runs == 0 {
+            encoding
+                .resources
+                .normalized_coords
+                .truncate(run.normalized_coords.start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::kurbo::Affine;
+    use peniko::{Blob, Color, Fill, Font};
+
+    use crate::{DrawTag, Encoding, Glyph, Patch, Transform};
+
+    #[test]
+    fn synthetic_oblique() {
+        let font = Font::new(Blob::from(vec![0_u8; 4]), 0);
+        let mut encoding = Encoding::new();
+        let scale = Transform::from_kurbo(&Affine::scale(2.0));
+        encoding
+            .encode_glyph_run(
+                &font,
+                12.0,
+                Transform::IDENTITY,
+                Some(scale),
+                Fill::NonZero,
+                Color::WHITE,
+                false,
+                &[],
+            )
+            .embolden(0.5)
+            .skew(0.25_f32.atan())
+            .glyphs([Glyph::default()]);
+        let run = &encoding.resources.glyph_runs[0];
+        assert_eq!(run.embolden, 0.5);
+        // The shear slants the glyph before it is scaled.
+        let expected = Transform::from_kurbo(&Affine::new([2.0, 0.0, 0.5, 2.0, 0.0, 0.0]));
+        assert_eq!(run.effective_glyph_transform(), Some(expected));
+    }
+
+    #[test]
+    fn encode_glyph_run() {
+        let font = Font::new(Blob::from(vec![0_u8; 4]), 0);
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::from_kurbo(&Affine::scale(2.0)));
+        let offsets = encoding.stream_offsets();
+        let glyphs = (0..3).map(|id| Glyph {
+            id,
+            x: id as f32 * 10.0,
+            y: 0.0,
+        });
+        encoding
+            .encode_glyph_run(
+                &font,
+                12.0,
+                Transform::IDENTITY,
+                None,
+                Fill::EvenOdd,
+                Color::WHITE,
+                true,
+                &[1, 2],
+            )
+            .brush_alpha(0.5)
+            .glyphs(glyphs);
+        let resources = &encoding.resources;
+        assert_eq!(resources.glyphs.len(), 3);
+        assert_eq!(resources.normalized_coords, [1, 2]);
+        let run = &resources.glyph_runs[0];
+        assert_eq!(run.glyphs, 0..3);
+        assert_eq!(run.normalized_coords, 0..2);
+        assert_eq!(run.stream_offsets.transforms, offsets.transforms);
+        assert!(run.hint);
+        assert!(matches!(
+            resources.patches[..],
+            [Patch::GlyphRun { index: 0 }]
+        ));
+        assert!(encoding.draw_tags == [DrawTag::COLOR]);
+        assert_eq!(run.effective_glyph_transform(), None);
+        // An empty run leaves no trace.
+        encoding
+            .encode_glyph_run(
+                &font,
+                12.0,
+                Transform::IDENTITY,
+                None,
+                Fill::NonZero,
+                Color::WHITE,
+                false,
+                &[3],
+            )
+            .glyphs([]);
+        assert_eq!(encoding.resources.glyph_runs.len(), 1);
+        assert_eq!(encoding.resources.normalized_coords.len(), 2);
+        assert_eq!(encoding.draw_tags.len(), 1);
+    }
+}
@@ -17,71 +17,6 @@ use dashmap::DashMap;
 use glyphon::TextBounds;
 use peniko::{Font, Style};
 
-use super::{Encoding, StreamOffsets};
-
-/// Command for vector outline construction
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-enum OutlineCommand {
-    MoveTo(f32, f32),
-    LineTo(f32, f32),
-    QuadTo(f32, f32, f32, f32),
-    CubicTo(f32, f32, f32, f32, f32, f32),
-    Close,
-}
-
-/// Collector that captures outline commands for later replay
-#[allow(dead_code)]
-struct OutlineCommandCollector<'a> {
-    scale: f32,
-    commands: &'a mut Vec<OutlineCommand>,
-}
-
-impl<'a> OutlineCommandCollector<'a> {
-    #[allow(dead_code)]
-    fn new(scale: f32, commands: &'a mut Vec<OutlineCommand>) -> Self {
-        Self { scale, commands }
-    }
-}
-
-impl<'a> ttf_parser::OutlineBuilder for OutlineCommandCollector<'a> {
-    fn move_to(&mut self, x: f32, y: f32) {
-        // CRITICAL FIX: Remove Y-axis flipping to match original skrifa behavior
-        // The coordinate transformation should happen at the render level, not here
-        self.commands
-            .push(OutlineCommand::MoveTo(x * self.scale, y * self.scale));
-    }
-
-    fn line_to(&mut self, x: f32, y: f32) {
-        self.commands
-            .push(OutlineCommand::LineTo(x * self.scale, y * self.scale));
-    }
-
-    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
-        self.commands.push(OutlineCommand::QuadTo(
-            x1 * self.scale,
-            y1 * self.scale,
-            x * self.scale,
-            y * self.scale,
-        ));
-    }
-
-    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
-        self.commands.push(OutlineCommand::CubicTo(
-            x1 * self.scale,
-            y1 * self.scale,
-            x2 * self.scale,
-            y2 * self.scale,
-            x * self.scale,
-            y * self.scale,
-        ));
-    }
-
-    fn close(&mut self) {
-        self.commands.push(OutlineCommand::Close);
-    }
-}
-
 /// Cache key for text rendering operations
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
 struct TextCacheKey {
@@ -103,8 +38,6 @@ pub(crate) struct GlyphCache {
     prepared_text_cache: Arc<DashMap<TextCacheKey, Arc<PreparedText>>>,
     /// Performance monitoring
     performance_monitor: Arc<AtomicU64>,
-    /// Cache for reusable encodings
-    encoding_pool: Arc<DashMap<u64, Arc<Encoding>>>,
     /// Statistics tracking
     stats: GlyphCacheStats,
 }
@@ -115,7 +48,6 @@ struct GlyphCacheStats {
     cache_hits: AtomicU64,
     cache_misses: AtomicU64,
     text_preparations: AtomicU64,
-    encoding_reuses: AtomicU64,
 }
 
 impl GlyphCache {
@@ -131,7 +63,6 @@ impl GlyphCache {
             format,
             prepared_text_cache: Arc::new(DashMap::new()),
             performance_monitor: Arc::new(AtomicU64::new(0)),
-            encoding_pool: Arc::new(DashMap::new()),
             stats: GlyphCacheStats::default(),
         })
     }
@@ -151,7 +82,6 @@ impl GlyphCache {
             format,
             prepared_text_cache: Arc::new(DashMap::new()),
             performance_monitor: Arc::new(AtomicU64::new(0)),
-            encoding_pool: Arc::new(DashMap::new()),
             stats: GlyphCacheStats::default(),
         })
     }
@@ -221,19 +151,17 @@ impl GlyphCache {
             });
 
             self.prepared_text_cache
-                .insert(cache_key.clone(), prepared.clone());
+                .insert(cache_key, prepared.clone());
             self.stats.text_preparations.fetch_add(1, Ordering::Relaxed);
             prepared
         };
 
         Ok(GlyphCacheSession {
-            cache: self,
             font,
             size,
             style,
             text,
             prepared_text,
-            cache_key,
         })
     }
 
@@ -290,38 +218,24 @@ impl GlyphCache {
                 self.prepared_text_cache.remove(&key);
             }
         }
-
-        // Periodically trim encoding pool
-        if self.encoding_pool.len() > 500 {
-            // Keep most recent 400 encodings
-            let to_remove: Vec<_> = self
-                .encoding_pool
-                .iter()
-                .take(self.encoding_pool.len() - 400)
-                .map(|entry| *entry.key())
-                .collect();
-
-            for key in to_remove {
-                self.encoding_pool.remove(&key);
-            }
-        }
     }
 
     /// Get cache statistics
     #[allow(dead_code)]
-    pub(crate) fn stats(&self) -> (u64, u64, u64, u64) {
+    pub(crate) fn stats(&self) -> (u64, u64, u64) {
         (
             self.stats.cache_hits.load(Ordering::Relaxed),
             self.stats.cache_misses.load(Ordering::Relaxed),
             self.stats.text_preparations.load(Ordering::Relaxed),
-            self.stats.encoding_reuses.load(Ordering::Relaxed),
         )
     }
 }
 
 /// Session for text rendering operations using blitz-text
+///
+/// Glyph outlines are encoded from the font data by the resolver, so the
+/// session only provides the measurements of the prepared text.
 pub(crate) struct GlyphCacheSession<'a> {
-    cache: &'a mut GlyphCache,
     /// Font parameters for text rendering
     #[allow(dead_code)]
     font: &'a Font,
@@ -329,14 +243,14 @@ pub(crate) struct GlyphCacheSession<'a> {
     #[allow(dead_code)]
     size: f32,
     /// Font style configuration
+    #[allow(dead_code)]
     style: &'a Style,
     /// Text content to render
     #[allow(dead_code)]
     text: &'a str,
     /// Prepared text with measurements and layout
+    #[allow(dead_code)]
     prepared_text: Arc<PreparedText>,
-    /// Cache key for this session
-    cache_key: TextCacheKey,
 }
 
 impl<'a> GlyphCacheSession<'a> {
@@ -351,88 +265,4 @@ impl<'a> GlyphCacheSession<'a> {
     pub(crate) fn preparation_time(&self) -> std::time::Duration {
         self.prepared_text.preparation_time
     }
-    /// Get or create encoding for text rendering
-    pub(crate) fn get_encoding(&mut self) -> Arc<Encoding> {
-        // Generate encoding key based on cache key
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-
-        let mut hasher = DefaultHasher::new();
-        self.cache_key.hash(&mut hasher);
-        let encoding_key = hasher.finish();
-
-        // Try to reuse cached encoding
-        if let Some(cached) = self.cache.encoding_pool.get(&encoding_key) {
-            self.cache
-                .stats
-                .encoding_reuses
-                .fetch_add(1, Ordering::Relaxed);
-            return cached.clone();
-        }
-
-        // Create new encoding
-        let encoding = Arc::new(Encoding::new());
-        self.cache
-            .encoding_pool
-            .insert(encoding_key, encoding.clone());
-        encoding
-    }
-
-    /// Return encoding to cache for reuse - handled automatically
-    #[allow(dead_code)]
-    pub(crate) fn return_encoding(&mut self, _encoding: Arc<Encoding>) {
-        // Encoding caching is handled automatically in get_encoding method
-    }
-
-    /// Get text encoding using blitz-text measurement system
-    #[inline(always)]
-    pub(crate) fn get_text_encoding(&mut self) -> (Arc<Encoding>, StreamOffsets) {
-        // Get or create encoding
-        let mut encoding = self.get_encoding();
-        let encoding_ptr = Arc::make_mut(&mut encoding);
-        encoding_ptr.reset();
-
-        // Use text measurement for proper text layout
-        let measurement = &self.prepared_text.measurement;
-
-        // Encode style based on peniko style
-        let is_fill = match self.style {
-            Style::Fill(fill) => {
-                encoding_ptr.encode_fill_style(*fill);
-                true
-            }
-            Style::Stroke(stroke) => {
-                if encoding_ptr.encode_stroke_style(stroke) {
-                    false
-                } else {
-                    encoding_ptr.encode_fill_style(peniko::Fill::NonZero);
-                    true
-                }
-            }
-        };
-
-        // Create path encoder for text
-        let mut path = encoding_ptr.encode_path(is_fill);
-
-        // Create text outline based on text measurement
-        // For now, we'll create a simple rectangle representing the text bounds
-        let width = measurement.content_width;
-        let height = measurement.content_height;
-
-        // Create a simple rectangle representing the entire text (placeholder)
-        // In a full implementation, this would extract actual glyph outlines from font data
-        path.move_to(0.0, 0.0);
-        path.line_to(width, 0.0);
-        path.line_to(width, height);
-        path.line_to(0.0, height);
-        path.close();
-
-        let path_segments = path.finish(true);
-        if path_segments == 0 {
-            encoding_ptr.reset();
-        }
-
-        let stream_offsets = encoding_ptr.stream_offsets();
-        (encoding, stream_offsets)
-    }
 }
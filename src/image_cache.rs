@@ -2,81 +2,726 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
 use std::collections::HashMap;
-use std::collections::hash_map::Entry;
 
-use guillotiere::{AtlasAllocator, size2};
-use peniko::Image;
+use guillotiere::{AllocId, AtlasAllocator, size2};
+use peniko::{Blob, Extend, Image};
+
+use crate::pixel_format::{AlphaType, ImageError, PixelFormat, convert_image};
 
 const DEFAULT_ATLAS_SIZE: i32 = 1024;
 const MAX_ATLAS_SIZE: i32 = 8192;
+/// Default number of border pixels around each image, which covers the
+/// footprint of bicubic sampling.
+pub(crate) const DEFAULT_IMAGE_PADDING: u32 = 2;
+/// Maximum number of atlas pages, which matches the default limit on texture
+/// array layers in wgpu.
+const MAX_ATLAS_PAGES: usize = 256;
 
+/// Images placed in the atlas, which is a texture array with a layer for
+/// each page.
+///
+/// All pages have the same size. The atlas only has more than one page once
+/// the first page reached its maximum size.
 #[derive(Default)]
 pub struct Images<'a> {
     pub width: u32,
     pub height: u32,
-    pub images: &'a [(Image, u32, u32)],
+    /// Layout of each page of the atlas, indexed by the page index encoded in
+    /// the draw data.
+    pub pages: &'a [AtlasPage],
+    /// Images of the resolved encoding that couldn't be converted to the
+    /// atlas format, which aren't drawn.
+    pub errors: &'a [(Image, ImageError)],
+}
+
+impl Images<'_> {
+    /// Returns the size in bytes of a buffer that holds all pages, with rows
+    /// of `stride` bytes.
+    pub fn buffer_size(&self, stride: usize) -> usize {
+        stride * self.height as usize * self.pages.len()
+    }
+
+    /// Writes all images in use and their borders into `out`, converted to
+    /// premultiplied RGBA8.
+    ///
+    /// The buffer holds the pages one after another, each with `height` rows
+    /// of `stride` bytes, which matches the layers of the atlas texture
+    /// array. Pixels that aren't covered by an image are left untouched.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is shorter than a row of the atlas or `out` is
+    /// shorter than [`buffer_size`](Self::buffer_size).
+    pub fn compose_into(&self, out: &mut [u8], stride: usize) {
+        self.compose_pages(out, stride, |page| &page.images);
+    }
+
+    /// Writes only the images placed by the latest resolve and their borders
+    /// into `out`, like [`compose_into`](Self::compose_into), and returns the
+    /// rectangles that changed.
+    ///
+    /// Keeping the buffer across resolves and uploading the returned
+    /// rectangles keeps the atlas texture up to date. When the atlas grows or
+    /// gains a page, every image in use is written again, but the buffer has
+    /// to be resized by the caller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is shorter than a row of the atlas or `out` is
+    /// shorter than [`buffer_size`](Self::buffer_size).
+    pub fn compose_uploads_into(&self, out: &mut [u8], stride: usize) -> Vec<AtlasRect> {
+        self.compose_pages(out, stride, |page| &page.uploads);
+        self.pages
+            .iter()
+            .zip(0..)
+            .flat_map(|(page, index)| {
+                page.uploads.iter().map(move |upload| AtlasRect {
+                    page: index,
+                    x: upload.x - upload.padding,
+                    y: upload.y - upload.padding,
+                    width: upload.image.width + upload.padding * 2,
+                    height: upload.image.height + upload.padding * 2,
+                })
+            })
+            .collect()
+    }
+
+    fn compose_pages(
+        &self,
+        out: &mut [u8],
+        stride: usize,
+        images: impl Fn(&AtlasPage) -> &[AtlasImage],
+    ) {
+        assert!(
+            stride >= self.width as usize * 4,
+            "stride is shorter than an atlas row"
+        );
+        assert!(
+            out.len() >= self.buffer_size(stride),
+            "buffer is too small for the atlas"
+        );
+        let page_size = stride * self.height as usize;
+        if page_size == 0 {
+            return;
+        }
+        for (page, out) in self.pages.iter().zip(out.chunks_mut(page_size)) {
+            for image in images(page) {
+                image.compose_into(out, stride);
+            }
+        }
+    }
+}
+
+/// Rectangle on an atlas page, in pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AtlasRect {
+    pub page: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Layout of a single atlas page.
+#[derive(Clone, Default)]
+pub struct AtlasPage {
+    /// Images on this page used by the resolved encoding.
+    pub images: Vec<AtlasImage>,
+    /// Images that have to be written to this page, including their borders.
+    ///
+    /// Images stay in the atlas across resolves, so these are only the
+    /// images placed by the latest resolve. When the atlas grows or gains a
+    /// page, all images in use have to be uploaded again.
+    pub uploads: Vec<AtlasImage>,
+}
+
+/// Image placed on an atlas page.
+///
+/// Each image is surrounded by `padding` border pixels, so that filtered
+/// samples near its edges never read neighbouring images. The border is
+/// filled from the image itself according to its extend modes.
+#[derive(Clone, Debug)]
+pub struct AtlasImage {
+    /// The image, with its data converted to premultiplied RGBA8.
+    pub image: Image,
+    /// Horizontal position of the first image pixel, after the border.
+    pub x: u32,
+    /// Vertical position of the first image pixel, after the border.
+    pub y: u32,
+    /// Width of the border on each side of the image.
+    pub padding: u32,
+    /// Fill policy of the left and right borders.
+    pub x_fill: BorderFill,
+    /// Fill policy of the top and bottom borders.
+    pub y_fill: BorderFill,
 }
 
+impl AtlasImage {
+    /// Writes the image and its border into a page with rows of `stride`
+    /// bytes.
+    fn compose_into(&self, page: &mut [u8], stride: usize) {
+        let pixels = self.image.data.data();
+        let (width, height) = (self.image.width, self.image.height);
+        // Empty images have no pixels to fill their border from.
+        if width == 0 || height == 0 {
+            return;
+        }
+        let padding = i64::from(self.padding);
+        for dy in -padding..i64::from(height) + padding {
+            let src_row = self.y_fill.source(dy, height) as usize * width as usize;
+            let row = (i64::from(self.y) + dy) as usize * stride;
+            for dx in -padding..i64::from(width) + padding {
+                let src = (src_row + self.x_fill.source(dx, width) as usize) * 4;
+                let dst = row + (i64::from(self.x) + dx) as usize * 4;
+                page[dst..dst + 4].copy_from_slice(&pixels[src..src + 4]);
+            }
+        }
+    }
+}
+
+/// How the border pixels around an atlas image are filled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BorderFill {
+    /// Repeats the edge pixels, for [`Extend::Pad`].
+    #[default]
+    Clamp,
+    /// Continues with the pixels from the opposite edge, for
+    /// [`Extend::Repeat`].
+    Wrap,
+    /// Mirrors the pixels at the edge, for [`Extend::Reflect`].
+    Mirror,
+}
+
+impl BorderFill {
+    /// Returns the image pixel that fills the pixel at `index`, which is
+    /// relative to the first pixel of an image `len` pixels long and may lie
+    /// in the border on either side.
+    pub fn source(self, index: i64, len: u32) -> u32 {
+        let len = i64::from(len.max(1));
+        let source = match self {
+            Self::Clamp => index.clamp(0, len - 1),
+            Self::Wrap => index.rem_euclid(len),
+            Self::Mirror => {
+                let index = index.rem_euclid(2 * len);
+                if index < len {
+                    index
+                } else {
+                    2 * len - 1 - index
+                }
+            }
+        };
+        source as u32
+    }
+}
+
+impl From<Extend> for BorderFill {
+    fn from(extend: Extend) -> Self {
+        match extend {
+            Extend::Pad => Self::Clamp,
+            Extend::Repeat => Self::Wrap,
+            Extend::Reflect => Self::Mirror,
+        }
+    }
+}
+
+/// Location of an image in the atlas.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct AtlasLocation {
+    pub(crate) page: u32,
+    pub(crate) x: u32,
+    pub(crate) y: u32,
+}
+
+struct AtlasEntry {
+    alloc_id: AllocId,
+    image: AtlasImage,
+    location: AtlasLocation,
+    /// Epoch of the last resolve that used the image.
+    last_used: u64,
+}
+
+/// Atlas that keeps images in place across resolves.
+///
+/// Images are only evicted when a new image doesn't fit, starting with the
+/// least recently used one. Images used by the current resolve are never
+/// evicted, so the atlas grows instead, and gains pages once it reached its
+/// maximum size.
 pub(crate) struct ImageCache {
-    atlas: AtlasAllocator,
-    /// Map from image blob id to atlas location.
-    map: HashMap<u64, (u32, u32)>,
-    /// List of all allocated images with associated atlas location.
-    images: Vec<(Image, u32, u32)>,
+    /// Allocator for each page.
+    atlases: Vec<AtlasAllocator>,
+    /// Map from image blob id and border fill policies to atlas entry.
+    ///
+    /// The same image is placed once for each combination of fill policies,
+    /// since they change its border.
+    map: HashMap<(u64, BorderFill, BorderFill), AtlasEntry>,
+    /// Images used and placed by the current resolve, for each page.
+    pages: Vec<AtlasPage>,
+    /// Images of the current resolve that couldn't be converted.
+    errors: Vec<(Image, ImageError)>,
+    /// Pixel formats of image blobs that don't use the format of their
+    /// image, by blob id.
+    formats: HashMap<u64, (PixelFormat, AlphaType)>,
+    /// Width of the border around each image.
+    padding: u32,
+    /// Maximum width and height of a page.
+    max_size: i32,
+    epoch: u64,
 }
 
 impl Default for ImageCache {
     fn default() -> Self {
-        Self::new()
+        Self::new(DEFAULT_IMAGE_PADDING)
     }
 }
 
 impl ImageCache {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(padding: u32) -> Self {
+        Self::with_max_size(padding, MAX_ATLAS_SIZE)
+    }
+
+    pub(crate) fn with_max_size(padding: u32, max_size: i32) -> Self {
+        let size = DEFAULT_ATLAS_SIZE.min(max_size);
         Self {
-            atlas: AtlasAllocator::new(size2(DEFAULT_ATLAS_SIZE, DEFAULT_ATLAS_SIZE)),
+            atlases: vec![AtlasAllocator::new(size2(size, size))],
             map: HashMap::default(),
-            images: Vec::default(),
+            pages: vec![AtlasPage::default()],
+            errors: Vec::new(),
+            formats: HashMap::default(),
+            padding,
+            max_size,
+            epoch: 0,
         }
     }
 
+    /// Sets the pixel format of the images with the given data, dropping
+    /// them from the atlas if they were placed with another format.
+    pub(crate) fn set_format(&mut self, data: &Blob<u8>, format: PixelFormat, alpha: AlphaType) {
+        let id = data.id();
+        if self.formats.insert(id, (format, alpha)) == Some((format, alpha)) {
+            return;
+        }
+        let atlases = &mut self.atlases;
+        self.map.retain(|key, entry| {
+            if key.0 != id {
+                return true;
+            }
+            atlases[entry.location.page as usize].deallocate(entry.alloc_id);
+            false
+        });
+    }
+
     pub(crate) fn images(&self) -> Images<'_> {
+        let size = self.atlases[0].size();
         Images {
-            width: self.atlas.size().width as u32,
-            height: self.atlas.size().height as u32,
-            images: &self.images,
+            width: size.width as u32,
+            height: size.height as u32,
+            pages: &self.pages,
+            errors: &self.errors,
         }
     }
 
-    pub(crate) fn bump_size(&mut self) -> bool {
-        let new_size = self.atlas.size().width * 2;
-        if new_size > MAX_ATLAS_SIZE {
+    /// Starts a new resolve, which keeps the atlas but forgets which images
+    /// were used and placed.
+    pub(crate) fn maintain(&mut self) {
+        self.epoch += 1;
+        self.errors.clear();
+        for page in &mut self.pages {
+            page.images.clear();
+            page.uploads.clear();
+        }
+    }
+
+    pub(crate) fn get_or_insert(&mut self, image: &Image) -> Option<AtlasLocation> {
+        let (x_fill, y_fill) = (image.x_extend.into(), image.y_extend.into());
+        let key = (image.data.id(), x_fill, y_fill);
+        if let Some(entry) = self.map.get_mut(&key) {
+            let location = entry.location;
+            if entry.last_used != self.epoch {
+                entry.last_used = self.epoch;
+                self.pages[location.page as usize]
+                    .images
+                    .push(entry.image.clone());
+            }
+            return Some(location);
+        }
+        // Empty images have nothing to draw, and the allocator can never
+        // place them without padding.
+        if image.width == 0 || image.height == 0 {
+            return None;
+        }
+        let (width, height) = (
+            image.width.saturating_add(self.padding * 2),
+            image.height.saturating_add(self.padding * 2),
+        );
+        if width > self.max_size as u32 || height > self.max_size as u32 {
+            return None;
+        }
+        let converted = match convert_image(image, self.formats.get(&key.0).copied()) {
+            Ok(converted) => converted,
+            Err(err) => {
+                self.errors.push((image.clone(), err));
+                return None;
+            }
+        };
+        let size = size2(width as _, height as _);
+        let (page, alloc) = loop {
+            let alloc = self
+                .atlases
+                .iter_mut()
+                .enumerate()
+                .find_map(|(page, atlas)| Some((page, atlas.allocate(size)?)));
+            if let Some(alloc) = alloc {
+                break alloc;
+            }
+            if !self.evict_lru() && !self.grow() && !self.add_page() {
+                return None;
+            }
+        };
+        let location = AtlasLocation {
+            page: page as u32,
+            x: alloc.rectangle.min.x as u32 + self.padding,
+            y: alloc.rectangle.min.y as u32 + self.padding,
+        };
+        let atlas_image = AtlasImage {
+            image: converted,
+            x: location.x,
+            y: location.y,
+            padding: self.padding,
+            x_fill,
+            y_fill,
+        };
+        let page = &mut self.pages[page];
+        page.images.push(atlas_image.clone());
+        page.uploads.push(atlas_image.clone());
+        self.map.insert(
+            key,
+            AtlasEntry {
+                alloc_id: alloc.id,
+                image: atlas_image,
+                location,
+                last_used: self.epoch,
+            },
+        );
+        Some(location)
+    }
+
+    /// Evicts the least recently used image that isn't used by the current
+    /// resolve. Returns false if there is none.
+    fn evict_lru(&mut self) -> bool {
+        let Some((&id, _)) = self
+            .map
+            .iter()
+            .filter(|(_, entry)| entry.last_used != self.epoch)
+            .min_by_key(|(_, entry)| entry.last_used)
+        else {
+            return false;
+        };
+        let entry = self.map.remove(&id).unwrap();
+        self.atlases[entry.location.page as usize].deallocate(entry.alloc_id);
+        true
+    }
+
+    /// Doubles the size of the atlas, keeping all images in place. Returns
+    /// false if the atlas is at its maximum size.
+    ///
+    /// Only called when all remaining images are in use, which are all
+    /// uploaded again since the atlas texture is recreated.
+    fn grow(&mut self) -> bool {
+        // Only a single page atlas can grow, since pages are only added
+        // once the first one is at its maximum size.
+        let [atlas] = &mut self.atlases[..] else {
+            return false;
+        };
+        let new_size = atlas.size().width * 2;
+        if new_size > self.max_size {
+            return false;
+        }
+        atlas.grow(size2(new_size, new_size));
+        self.reupload();
+        true
+    }
+
+    /// Adds an empty page of the maximum size. Returns false if the atlas
+    /// already has the maximum number of pages.
+    ///
+    /// Like growing, this recreates the atlas texture, so all images in use
+    /// are uploaded again.
+    fn add_page(&mut self) -> bool {
+        if self.atlases.len() >= MAX_ATLAS_PAGES {
             return false;
         }
-        self.atlas = AtlasAllocator::new(size2(new_size, new_size));
-        self.map.clear();
-        self.images.clear();
+        self.atlases
+            .push(AtlasAllocator::new(size2(self.max_size, self.max_size)));
+        self.pages.push(AtlasPage::default());
+        self.reupload();
         true
     }
 
-    pub(crate) fn clear(&mut self) {
-        self.atlas.clear();
-        self.map.clear();
-        self.images.clear();
+    fn reupload(&mut self) {
+        for page in &mut self.pages {
+            page.uploads.clone_from(&page.images);
+        }
     }
+}
 
-    pub(crate) fn get_or_insert(&mut self, image: &Image) -> Option<(u32, u32)> {
-        match self.map.entry(image.data.id()) {
-            Entry::Occupied(occupied) => Some(*occupied.get()),
-            Entry::Vacant(vacant) => {
-                let alloc = self
-                    .atlas
-                    .allocate(size2(image.width as _, image.height as _))?;
-                let x = alloc.rectangle.min.x as u32;
-                let y = alloc.rectangle.min.y as u32;
-                self.images.push((image.clone(), x, y));
-                Some(*vacant.insert((x, y)))
+#[cfg(test)]
+mod tests {
+    use peniko::{Blob, Extend, Image, ImageFormat};
+
+    use crate::pixel_format::{AlphaType, ImageError, PixelFormat};
+
+    use super::{
+        AtlasImage, AtlasLocation, AtlasRect, BorderFill, DEFAULT_ATLAS_SIZE,
+        DEFAULT_IMAGE_PADDING, ImageCache, MAX_ATLAS_SIZE,
+    };
+
+    fn image(width: u32, height: u32) -> Image {
+        let data = vec![0_u8; (width * height * 4) as usize];
+        Image::new(Blob::from(data), ImageFormat::Rgba8, width, height)
+    }
+
+    #[test]
+    fn persistent_atlas() {
+        let mut cache = ImageCache::default();
+        let (a, b) = (image(16, 16), image(8, 8));
+        cache.maintain();
+        let a_location = cache.get_or_insert(&a).unwrap();
+        assert_eq!(cache.get_or_insert(&a), Some(a_location));
+        assert_eq!(cache.images().pages[0].uploads.len(), 1);
+        assert_eq!(cache.images().pages[0].images.len(), 1);
+        // Images stay in place and only new ones are uploaded.
+        cache.maintain();
+        let b_location = cache.get_or_insert(&b).unwrap();
+        assert_eq!(cache.get_or_insert(&a), Some(a_location));
+        let page = &cache.images().pages[0];
+        assert_eq!(page.images.len(), 2);
+        assert!(matches!(
+            &page.uploads[..],
+            [upload] if (upload.x, upload.y) == (b_location.x, b_location.y)
+        ));
+        cache.maintain();
+        assert_eq!(cache.get_or_insert(&b), Some(b_location));
+        assert!(cache.images().pages[0].uploads.is_empty());
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = ImageCache::new(0);
+        let half = DEFAULT_ATLAS_SIZE as u32 / 2;
+        let [a, b, c] = [(); 3].map(|_| image(half * 2, half));
+        cache.maintain();
+        cache.get_or_insert(&a).unwrap();
+        cache.maintain();
+        cache.get_or_insert(&b).unwrap();
+        // The atlas is full, so the image that wasn't used for the longest
+        // time makes room.
+        cache.maintain();
+        cache.get_or_insert(&b).unwrap();
+        cache.get_or_insert(&c).unwrap();
+        let images = cache.images();
+        assert_eq!(images.width, DEFAULT_ATLAS_SIZE as u32);
+        assert_eq!(images.pages[0].uploads.len(), 1);
+        let contains = |image: &Image| cache.map.keys().any(|key| key.0 == image.data.id());
+        assert!(contains(&b));
+        assert!(!contains(&a));
+    }
+
+    #[test]
+    fn growth() {
+        let mut cache = ImageCache::new(0);
+        let size = DEFAULT_ATLAS_SIZE as u32;
+        let (a, b) = (image(size, size), image(16, 16));
+        cache.maintain();
+        let a_location = cache.get_or_insert(&a).unwrap();
+        cache.maintain();
+        // Images in use are never evicted, so the atlas grows and everything
+        // in use is uploaded again.
+        assert_eq!(cache.get_or_insert(&a), Some(a_location));
+        cache.get_or_insert(&b).unwrap();
+        let images = cache.images();
+        assert_eq!(images.width, size * 2);
+        assert_eq!(images.pages[0].uploads.len(), 2);
+        // Images larger than the maximum atlas size never fit.
+        assert_eq!(cache.get_or_insert(&image(16_384, 1)), None);
+        assert_eq!(cache.images().width, size * 2);
+    }
+
+    #[test]
+    fn empty_images() {
+        let mut cache = ImageCache::new(0);
+        cache.maintain();
+        assert_eq!(cache.get_or_insert(&image(0, 16)), None);
+        assert_eq!(cache.get_or_insert(&image(16, 0)), None);
+        // Nothing was evicted or grown trying to place them.
+        let images = cache.images();
+        assert_eq!(images.width, DEFAULT_ATLAS_SIZE as u32);
+        assert_eq!(images.pages.len(), 1);
+        assert!(images.pages[0].images.is_empty());
+    }
+
+    #[test]
+    fn pages() {
+        // Pages of the real maximum size would need huge images.
+        let max = 64;
+        let mut cache = ImageCache::with_max_size(0, max as i32);
+        let [a, b, c] = [(); 3].map(|_| image(max, max));
+        cache.maintain();
+        let a_location = cache.get_or_insert(&a).unwrap();
+        assert_eq!(cache.images().width, max);
+        assert_eq!(cache.images().pages.len(), 1);
+        // A full atlas at its maximum size gains a page instead of dropping
+        // images in use.
+        let b_location = cache.get_or_insert(&b).unwrap();
+        assert_eq!(
+            b_location,
+            AtlasLocation {
+                page: 1,
+                x: 0,
+                y: 0
             }
+        );
+        let images = cache.images();
+        assert_eq!((images.width, images.height), (max, max));
+        assert_eq!(images.pages.len(), 2);
+        assert!(
+            images
+                .pages
+                .iter()
+                .all(|page| page.images.len() == 1 && page.uploads.len() == 1)
+        );
+        // Unused images are still evicted before adding another page.
+        cache.maintain();
+        assert_eq!(cache.get_or_insert(&b), Some(b_location));
+        assert_eq!(cache.get_or_insert(&c).unwrap().page, a_location.page);
+        let images = cache.images();
+        assert_eq!(images.pages.len(), 2);
+        assert_eq!(images.pages[0].uploads.len(), 1);
+        assert!(images.pages[1].uploads.is_empty());
+    }
+
+    #[test]
+    fn padding() {
+        let mut cache = ImageCache::default();
+        let padding = DEFAULT_IMAGE_PADDING;
+        let a = image(16, 16);
+        let b = image(16, 16).with_extend(Extend::Repeat);
+        cache.maintain();
+        let a_location = cache.get_or_insert(&a).unwrap();
+        assert_eq!((a_location.x, a_location.y), (padding, padding));
+        // Images with other extend modes get their own border, and borders
+        // never overlap.
+        let b_location = cache.get_or_insert(&b).unwrap();
+        assert_ne!(a_location, b_location);
+        assert!(
+            b_location.x >= a_location.x + 16 + padding * 2
+                || b_location.y >= a_location.y + 16 + padding * 2
+        );
+        let uploads = &cache.images().pages[0].uploads;
+        assert_eq!(uploads[0].x_fill, BorderFill::Clamp);
+        assert_eq!(uploads[1].x_fill, BorderFill::Wrap);
+        assert!(uploads.iter().all(|upload| upload.padding == padding));
+        // The border counts towards the maximum size.
+        let max = MAX_ATLAS_SIZE as u32;
+        assert_eq!(cache.get_or_insert(&image(max, 1)), None);
+        assert!(ImageCache::new(0).get_or_insert(&image(max, 1)).is_some());
+    }
+
+    #[test]
+    fn border_fill() {
+        let sources = |fill: BorderFill| (-3..6).map(|ix| fill.source(ix, 3)).collect::<Vec<_>>();
+        assert_eq!(sources(BorderFill::Clamp), [0, 0, 0, 0, 1, 2, 2, 2, 2]);
+        assert_eq!(sources(BorderFill::Wrap), [0, 1, 2, 0, 1, 2, 0, 1, 2]);
+        assert_eq!(sources(BorderFill::Mirror), [2, 1, 0, 0, 1, 2, 2, 1, 0]);
+        assert_eq!(BorderFill::from(Extend::Reflect), BorderFill::Mirror);
+    }
+
+    #[test]
+    fn compose_empty() {
+        // Empty images never enter the atlas, but composing one placed by
+        // hand leaves its slot untouched.
+        let image = AtlasImage {
+            image: image(0, 3),
+            x: 2,
+            y: 2,
+            padding: 2,
+            x_fill: BorderFill::Clamp,
+            y_fill: BorderFill::Mirror,
+        };
+        let mut page = vec![0_u8; 8 * 8 * 4];
+        image.compose_into(&mut page, 8 * 4);
+        assert!(page.iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn compose() {
+        let mut cache = ImageCache::new(1);
+        let data = vec![255, 0, 0, 255, 0, 0, 255, 128];
+        let image =
+            Image::new(Blob::from(data), ImageFormat::Rgba8, 2, 1).with_x_extend(Extend::Repeat);
+        cache.maintain();
+        cache.get_or_insert(&image).unwrap();
+        let images = cache.images();
+        let stride = images.width as usize * 4;
+        let mut out = vec![0_u8; images.buffer_size(stride)];
+        let rects = images.compose_uploads_into(&mut out, stride);
+        assert_eq!(
+            rects,
+            [AtlasRect {
+                page: 0,
+                x: 0,
+                y: 0,
+                width: 4,
+                height: 3
+            }]
+        );
+        // The border wraps horizontally and clamps vertically, and the
+        // pixels are premultiplied.
+        let (red, blue) = ([255, 0, 0, 255], [0, 0, 128, 128]);
+        for row in out.chunks(stride).take(3) {
+            assert_eq!(row[..16], [blue, red, blue, red].concat());
+            assert!(row[16..].iter().all(|&c| c == 0));
         }
+        assert!(out[stride * 3..].iter().all(|&c| c == 0));
+        // Nothing changes in the next resolve, but all images in use can
+        // still be written from scratch.
+        cache.maintain();
+        cache.get_or_insert(&image).unwrap();
+        let images = cache.images();
+        assert!(images.compose_uploads_into(&mut out, stride).is_empty());
+        let mut fresh = vec![0_u8; images.buffer_size(stride)];
+        images.compose_into(&mut fresh, stride);
+        assert!(fresh == out);
+    }
+
+    #[test]
+    fn formats() {
+        let mut cache = ImageCache::new(0);
+        let data = Blob::from(vec![0_u8, 0, 255, 255]);
+        let image = Image::new(data.clone(), ImageFormat::Rgba8, 1, 1);
+        cache.maintain();
+        cache.get_or_insert(&image).unwrap();
+        let blue = |cache: &ImageCache| cache.images().pages[0].images[0].image.data.data()[2];
+        assert_eq!(blue(&cache), 255);
+        // Changing the format places the image again, converted from BGRA.
+        cache.set_format(&data, PixelFormat::Bgra8, AlphaType::Straight);
+        cache.maintain();
+        cache.get_or_insert(&image).unwrap();
+        assert_eq!(blue(&cache), 0);
+        assert_eq!(cache.images().pages[0].uploads.len(), 1);
+        // Images that can't be converted are reported instead of placed.
+        let truncated = Image::new(Blob::from(vec![0_u8; 4]), ImageFormat::Rgba8, 2, 2);
+        assert_eq!(cache.get_or_insert(&truncated), None);
+        assert!(matches!(
+            cache.images().errors,
+            [(
+                _,
+                ImageError::Truncated {
+                    expected: 16,
+                    actual: 4
+                }
+            )]
+        ));
+        cache.maintain();
+        assert!(cache.images().errors.is_empty());
     }
 }
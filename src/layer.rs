@@ -0,0 +1,192 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Scoped clip layers.
+
+use core::ops::Deref;
+use std::borrow::{Borrow, BorrowMut};
+
+use peniko::kurbo::{Affine, Shape};
+use peniko::{BlendMode, Fill};
+
+use super::{Encoding, SceneBuilder, Transform};
+
+impl Encoding {
+    /// Pushes a new layer clipped by `clip` and returns a guard that pops it
+    /// when dropped.
+    ///
+    /// This encodes `transform`, a non-zero fill style, the clip path and the
+    /// begin clip in the order the pipeline expects. Until the guard is
+    /// dropped, the encoding is only reachable through it, so everything
+    /// drawn in the meantime ends up inside the layer. The guard only offers
+    /// drawing and nested layers, so clips can't be left unbalanced and the
+    /// encoding can't be reset from inside it. If `clip` has no segments, an
+    /// empty path is encoded instead and the layer suppresses all drawing
+    /// until it is popped.
+    ///
+    /// `alpha` is clamped to the range `0.0..=1.0`.
+    #[must_use = "the layer is popped as soon as the guard is dropped"]
+    pub fn push_layer(
+        &mut self,
+        blend: impl Into<BlendMode>,
+        alpha: f32,
+        clip: &impl Shape,
+        transform: Transform,
+    ) -> LayerGuard<'_> {
+        let depth = self.n_open_clips;
+        self.encode_layer(blend.into(), alpha, clip, transform);
+        SceneBuilder {
+            encoding: LayerScope {
+                encoding: self,
+                depth,
+            },
+        }
+    }
+
+    /// Encodes the clip path and begin clip of a layer, leaving it open.
+    pub(crate) fn encode_layer(
+        &mut self,
+        blend: BlendMode,
+        alpha: f32,
+        clip: &impl Shape,
+        transform: Transform,
+    ) {
+        self.encode_transform(transform);
+        self.encode_fill_style(Fill::NonZero);
+        if !self.encode_shape(clip, true) {
+            self.encode_empty_shape();
+        }
+        self.encode_begin_clip(blend, alpha.clamp(0.0, 1.0));
+    }
+}
+
+/// A layer pushed with [`Encoding::push_layer`].
+///
+/// The contents of the layer, including nested layers, are drawn with the
+/// commands of [`SceneBuilder`]. The guard dereferences to the encoding for
+/// reading it. Dropping the guard encodes the end clip.
+pub type LayerGuard<'a> = SceneBuilder<LayerScope<'a>>;
+
+/// Encoding borrowed by a [`LayerGuard`] for the lifetime of the layer.
+pub struct LayerScope<'a> {
+    encoding: &'a mut Encoding,
+    /// Number of open clips before the layer was pushed.
+    depth: u32,
+}
+
+impl LayerGuard<'_> {
+    /// Pushes a nested layer clipped by `clip` in the coordinate space given
+    /// by `transform`. See [`Encoding::push_layer`].
+    #[must_use = "the layer is popped as soon as the guard is dropped"]
+    pub fn push_layer(
+        &mut self,
+        blend: impl Into<BlendMode>,
+        alpha: f32,
+        transform: Affine,
+        clip: &impl Shape,
+    ) -> LayerGuard<'_> {
+        self.encoding
+            .encoding
+            .push_layer(blend, alpha, clip, Transform::from_kurbo(&transform))
+    }
+
+    /// Pops the layer. This is equivalent to dropping the guard.
+    pub fn pop(self) {}
+}
+
+impl Deref for LayerGuard<'_> {
+    type Target = Encoding;
+
+    fn deref(&self) -> &Encoding {
+        self.encoding.encoding
+    }
+}
+
+impl Borrow<Encoding> for LayerScope<'_> {
+    fn borrow(&self) -> &Encoding {
+        self.encoding
+    }
+}
+
+impl BorrowMut<Encoding> for LayerScope<'_> {
+    fn borrow_mut(&mut self) -> &mut Encoding {
+        self.encoding
+    }
+}
+
+impl Drop for LayerScope<'_> {
+    fn drop(&mut self) {
+        while self.encoding.n_open_clips > self.depth {
+            self.encoding.encode_end_clip();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::kurbo::{Affine, Rect, Shape};
+    use peniko::{Color, Fill, Mix};
+
+    use crate::{DrawTag, Encoding, PathTag, Transform};
+
+    #[test]
+    fn nested_layers() {
+        let mut encoding = Encoding::new();
+        let transform = Transform::from_kurbo(&Affine::translate((5.0, 5.0)));
+        {
+            let mut outer =
+                encoding.push_layer(Mix::Normal, 2.0, &Rect::new(0.0, 0.0, 8.0, 8.0), transform);
+            outer.fill(
+                Fill::NonZero,
+                Affine::IDENTITY,
+                Color::WHITE,
+                None,
+                &Rect::new(1.0, 1.0, 2.0, 2.0),
+            );
+            let inner = outer.push_layer(Mix::Multiply, 0.5, Affine::IDENTITY, &Rect::ZERO);
+            assert_eq!(inner.n_open_clips, 2);
+            inner.pop();
+            assert_eq!(outer.n_open_clips, 1);
+        }
+        assert!(encoding.validate().is_ok());
+        assert_eq!(encoding.n_open_clips, 0);
+        assert!(
+            encoding.draw_tags
+                == [
+                    DrawTag::BEGIN_CLIP,
+                    DrawTag::COLOR,
+                    DrawTag::BEGIN_CLIP,
+                    DrawTag::END_CLIP,
+                    DrawTag::END_CLIP,
+                ]
+        );
+        assert_eq!(encoding.transforms, [transform, Transform::IDENTITY]);
+        assert!(encoding.path_tags[..2] == [PathTag::TRANSFORM, PathTag::STYLE]);
+        // The alpha of the outer layer is clamped.
+        assert_eq!(encoding.draw_data[1], 1.0_f32.to_bits());
+        // The empty clip shape is replaced with a placeholder path.
+        let paths: Vec<_> = encoding.decode_paths().collect();
+        assert_eq!(paths[2].0.bounding_box(), Rect::ZERO);
+    }
+
+    #[test]
+    fn layer_inside_open_clip() {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 8.0, 8.0), true);
+        encoding.encode_begin_clip(Mix::Clip.into(), 1.0);
+        encoding
+            .push_layer(
+                Mix::Normal,
+                1.0,
+                &Rect::new(0.0, 0.0, 4.0, 4.0),
+                Transform::IDENTITY,
+            )
+            .pop();
+        // Only the clip of the layer is ended with it.
+        assert_eq!(encoding.n_open_clips, 1);
+        encoding.encode_end_clip();
+        assert!(encoding.validate().is_ok());
+    }
+}
@@ -30,29 +30,48 @@
     reason = "Deferred, only apply in some feature sets so not expect"
 )]
 
+mod bbox;
 mod binning;
 mod clip;
+mod color_glyph;
 mod config;
+mod cull;
+mod decode;
+mod disasm;
 mod draw;
 mod encoding;
 #[cfg(feature = "bump_estimate")]
 mod estimate;
 mod glyph;
+#[cfg(feature = "gpu_text")]
 mod glyph_cache;
 mod image_cache;
+mod layer;
 mod mask;
 pub mod math;
 mod monoid;
+mod outline;
 mod path;
+mod pixel_format;
+mod provider;
 mod ramp_cache;
 mod resolve;
+mod scene;
+mod serialize;
+#[cfg(test)]
+mod test_font;
+mod text;
+mod validate;
 
+pub use bbox::SceneBboxes;
 pub use binning::BinHeader;
 pub use clip::{Clip, ClipBbox, ClipBic, ClipElement};
 pub use config::{
     BufferSize, BufferSizes, BumpAllocatorMemory, BumpAllocators, ConfigUniform, IndirectCount,
     RenderConfig, WorkgroupCounts, WorkgroupSize,
 };
+pub use decode::DecodedPaths;
+pub use disasm::AssembleError;
 pub use draw::{
     DRAW_INFO_FLAGS_FILL_RULE_BIT, DrawBbox, DrawBeginClip, DrawBlurRoundedRect, DrawColor,
     DrawImage, DrawLinearGradient, DrawMonoid, DrawRadialGradient, DrawSweepGradient, DrawTag,
@@ -60,7 +79,9 @@ pub use draw::{
 pub use encoding::{Encoding, Resources, StreamOffsets};
 #[cfg(feature = "bump_estimate")]
 pub use estimate::BumpEstimator;
-pub use glyph::{Glyph, GlyphRun};
+pub use glyph::{Glyph, GlyphRun, GlyphRunBuilder};
+pub use image_cache::{AtlasImage, AtlasPage, AtlasRect, BorderFill, Images};
+pub use layer::{LayerGuard, LayerScope};
 pub use mask::{make_mask_lut, make_mask_lut_16};
 pub use math::Transform;
 pub use monoid::Monoid;
@@ -68,8 +89,13 @@ pub use path::{
     Cubic, LineSoup, Path, PathBbox, PathEncoder, PathMonoid, PathSegment, PathSegmentType,
     PathTag, SegmentCount, Style, Tile,
 };
+pub use pixel_format::{AlphaType, ImageError, PixelFormat};
+pub use provider::{GlyphMetrics, GlyphProvider, TtfGlyphProvider};
 pub use ramp_cache::Ramps;
 pub use resolve::{Layout, Patch, Resolver, resolve_solid_paths_only};
+pub use scene::{DrawGlyphs, SceneBuilder};
+pub use serialize::{FORMAT_VERSION, ReadError};
+pub use validate::EncodingError;
 
 /// A normalized variation coordinate (for variable fonts) in 2.14 fixed point format.
 ///
@@ -0,0 +1,771 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Glyph outlines extracted from font data on the CPU.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use peniko::kurbo::{PathEl, Vec2};
+use peniko::{Fill, Font, Style};
+use ttf_parser::LazyArray16;
+use ttf_parser::avar::AxisValueMap;
+
+use super::{Encoding, NormalizedCoord, PathEncoder, StreamOffsets};
+use crate::provider::{GlyphProvider, TtfGlyphProvider};
+
+/// Command for vector outline construction
+#[derive(Debug, Clone)]
+pub(crate) enum OutlineCommand {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    QuadTo(f32, f32, f32, f32),
+    CubicTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+/// Collector that captures outline commands for later replay
+pub(crate) struct OutlineCommandCollector<'a> {
+    scale: f32,
+    commands: &'a mut Vec<OutlineCommand>,
+}
+
+impl<'a> OutlineCommandCollector<'a> {
+    pub(crate) fn new(scale: f32, commands: &'a mut Vec<OutlineCommand>) -> Self {
+        Self { scale, commands }
+    }
+}
+
+impl ttf_parser::OutlineBuilder for OutlineCommandCollector<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        // Outlines stay in the y-up font coordinate system. The glyph
+        // transform computed by the resolver flips them.
+        self.commands
+            .push(OutlineCommand::MoveTo(x * self.scale, y * self.scale));
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.commands
+            .push(OutlineCommand::LineTo(x * self.scale, y * self.scale));
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.commands.push(OutlineCommand::QuadTo(
+            x1 * self.scale,
+            y1 * self.scale,
+            x * self.scale,
+            y * self.scale,
+        ));
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.commands.push(OutlineCommand::CubicTo(
+            x1 * self.scale,
+            y1 * self.scale,
+            x2 * self.scale,
+            y2 * self.scale,
+            x * self.scale,
+            y * self.scale,
+        ));
+    }
+
+    fn close(&mut self) {
+        self.commands.push(OutlineCommand::Close);
+    }
+}
+
+/// Expands an outline in pixels outward by `strength` pixels.
+///
+/// Every point, including off-curve points, moves along the bisector of
+/// the normals of its adjacent edges, so that straight edges move by exactly
+/// `strength`. Outer contours grow and counters shrink, based on the winding
+/// direction of the outline as a whole.
+fn embolden_outline(commands: &mut [OutlineCommand], strength: f32) {
+    let mut area = 0.0;
+    let mut contours = Vec::new();
+    let mut start = 0;
+    for (ix, command) in commands.iter().enumerate() {
+        if ix > start && matches!(command, OutlineCommand::MoveTo(..)) {
+            contours.push(start..ix);
+            start = ix;
+        }
+    }
+    contours.push(start..commands.len());
+    let mut points = Vec::new();
+    let contour_points = |commands: &[OutlineCommand], points: &mut Vec<Vec2>| {
+        points.clear();
+        for command in commands {
+            match *command {
+                OutlineCommand::MoveTo(x, y) | OutlineCommand::LineTo(x, y) => {
+                    points.push(Vec2::new(x.into(), y.into()));
+                }
+                OutlineCommand::QuadTo(x1, y1, x, y) => {
+                    points.extend([(x1, y1), (x, y)].map(|(x, y)| Vec2::new(x.into(), y.into())));
+                }
+                OutlineCommand::CubicTo(x1, y1, x2, y2, x, y) => {
+                    points.extend(
+                        [(x1, y1), (x2, y2), (x, y)].map(|(x, y)| Vec2::new(x.into(), y.into())),
+                    );
+                }
+                OutlineCommand::Close => {}
+            }
+        }
+    };
+    for contour in &contours {
+        contour_points(&commands[contour.clone()], &mut points);
+        for (ix, point) in points.iter().enumerate() {
+            area += point.cross(points[(ix + 1) % points.len()]);
+        }
+    }
+    // Outward normals point right of the direction of counter-clockwise
+    // outer contours in the y-up space of the outline.
+    let sign = if area > 0.0 { 1.0 } else { -1.0 };
+    let normal = |d: Vec2| Vec2::new(d.y, -d.x) * sign;
+    let mut shifted = Vec::new();
+    for contour in contours {
+        let commands = &mut commands[contour];
+        contour_points(commands, &mut points);
+        // Closed contours commonly end on their start point.
+        let n = match (points.first(), points.last()) {
+            (Some(first), Some(last)) if points.len() > 1 && first == last => points.len() - 1,
+            _ => points.len(),
+        };
+        shifted.clear();
+        for ix in 0..n {
+            let point = points[ix];
+            // Skip coincident neighbors, which have no direction.
+            let before = (1..n)
+                .map(|offset| points[(ix + n - offset) % n])
+                .find(|p| *p != point);
+            let after = (1..n)
+                .map(|offset| points[(ix + offset) % n])
+                .find(|p| *p != point);
+            let (Some(before), Some(after)) = (before, after) else {
+                shifted.push(point);
+                continue;
+            };
+            let n_in = normal((point - before).normalize());
+            let n_out = normal((after - point).normalize());
+            let d = 1.0 + n_in.dot(n_out);
+            // Nearly reversing edges would move the point arbitrarily far.
+            let shift = if d > 0.25 {
+                (n_in + n_out) * (f64::from(strength) / d)
+            } else {
+                n_in * f64::from(strength)
+            };
+            shifted.push(point + shift);
+        }
+        if n < points.len() {
+            shifted.push(shifted[0]);
+        }
+        let mut shifted = shifted.iter().map(|p| (p.x as f32, p.y as f32));
+        let mut next = || shifted.next().unwrap_or_default();
+        for command in commands.iter_mut() {
+            *command = match *command {
+                OutlineCommand::MoveTo(..) => {
+                    let (x, y) = next();
+                    OutlineCommand::MoveTo(x, y)
+                }
+                OutlineCommand::LineTo(..) => {
+                    let (x, y) = next();
+                    OutlineCommand::LineTo(x, y)
+                }
+                OutlineCommand::QuadTo(..) => {
+                    let ((x1, y1), (x, y)) = (next(), next());
+                    OutlineCommand::QuadTo(x1, y1, x, y)
+                }
+                OutlineCommand::CubicTo(..) => {
+                    let ((x1, y1), (x2, y2), (x, y)) = (next(), next(), next());
+                    OutlineCommand::CubicTo(x1, y1, x2, y2, x, y)
+                }
+                OutlineCommand::Close => OutlineCommand::Close,
+            };
+        }
+    }
+}
+
+/// Aligns the horizontal edges of an outline in pixels to the pixel grid.
+///
+/// `ttf_parser` doesn't interpret TrueType instructions, so this is a light
+/// autohinter in the spirit of FreeType's "light" mode: only the vertical
+/// axis is fitted, which keeps advances and glyph shapes intact. Edges are
+/// the on-curve points with a horizontal tangent, such as the baseline,
+/// x-height and the flat parts of serifs. They are rounded to whole pixels
+/// and all other points are interpolated between the nearest edges.
+pub(crate) fn hint_outline(commands: &mut [OutlineCommand]) {
+    let mut edges = Vec::new();
+    let (mut start, mut current) = ((0.0, 0.0), (0.0, 0.0));
+    for command in commands.iter() {
+        // Control points adjacent to each end of the segment.
+        let (first, last, end) = match *command {
+            OutlineCommand::MoveTo(x, y) => {
+                start = (x, y);
+                current = start;
+                continue;
+            }
+            OutlineCommand::LineTo(x, y) => ((x, y), current, (x, y)),
+            OutlineCommand::QuadTo(x1, y1, x, y) => ((x1, y1), (x1, y1), (x, y)),
+            OutlineCommand::CubicTo(x1, y1, x2, y2, x, y) => ((x1, y1), (x2, y2), (x, y)),
+            OutlineCommand::Close => (start, current, start),
+        };
+        if first.1 == current.1 {
+            edges.push(current.1);
+        }
+        if last.1 == end.1 {
+            edges.push(end.1);
+        }
+        current = end;
+    }
+    if edges.is_empty() {
+        return;
+    }
+    edges.sort_by(f32::total_cmp);
+    edges.dedup();
+    let fitted = edges.iter().map(|y| y.round()).collect::<Vec<_>>();
+    let fit = |y: f32| {
+        let ix = edges.partition_point(|edge| *edge < y);
+        if ix == edges.len() {
+            y + fitted[ix - 1] - edges[ix - 1]
+        } else if edges[ix] == y {
+            fitted[ix]
+        } else if ix == 0 {
+            y + fitted[0] - edges[0]
+        } else {
+            let t = (y - edges[ix - 1]) / (edges[ix] - edges[ix - 1]);
+            fitted[ix - 1] + t * (fitted[ix] - fitted[ix - 1])
+        }
+    };
+    for command in commands.iter_mut() {
+        match command {
+            OutlineCommand::MoveTo(_, y) | OutlineCommand::LineTo(_, y) => *y = fit(*y),
+            OutlineCommand::QuadTo(_, y1, _, y) => {
+                *y1 = fit(*y1);
+                *y = fit(*y);
+            }
+            OutlineCommand::CubicTo(_, y1, _, y2, _, y) => {
+                *y1 = fit(*y1);
+                *y2 = fit(*y2);
+                *y = fit(*y);
+            }
+            OutlineCommand::Close => {}
+        }
+    }
+}
+
+/// Encodes outline commands into `path`, moved right by `x_offset`.
+pub(crate) fn encode_commands(
+    commands: &[OutlineCommand],
+    path: &mut PathEncoder<'_>,
+    x_offset: f32,
+) {
+    for command in commands {
+        match *command {
+            OutlineCommand::MoveTo(x, y) => path.move_to(x + x_offset, y),
+            OutlineCommand::LineTo(x, y) => path.line_to(x + x_offset, y),
+            OutlineCommand::QuadTo(x1, y1, x, y) => {
+                path.quad_to(x1 + x_offset, y1, x + x_offset, y)
+            }
+            OutlineCommand::CubicTo(x1, y1, x2, y2, x, y) => {
+                path.cubic_to(x1 + x_offset, y1, x2 + x_offset, y2, x + x_offset, y);
+            }
+            OutlineCommand::Close => path.close(),
+        }
+    }
+}
+
+/// Number of [`OutlineCache::maintain`] calls after which an unused glyph is
+/// evicted.
+const MAX_ENTRY_AGE: u64 = 64;
+
+/// Identifies an encoded glyph outline for a given set of variation
+/// coordinates.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font_id: u64,
+    font_index: u32,
+    glyph_id: u32,
+    font_size_bits: u32,
+    hint: bool,
+    /// Bits of the synthetic bold strength in pixels.
+    embolden_bits: u32,
+    /// Bits of the horizontal offset of the outline in pixels.
+    x_offset_bits: u32,
+    /// Bits of the encoded [`Style`](crate::Style).
+    style: [u32; 2],
+}
+
+struct GlyphEntry {
+    encoding: Arc<Encoding>,
+    stream_sizes: StreamOffsets,
+    /// Epoch of the last use.
+    epoch: u64,
+}
+
+/// Source of glyph outlines that needs no GPU context.
+///
+/// Outlines are requested from a [`GlyphProvider`] and cached per font,
+/// glyph, size, hinting, synthetic bold strength, subpixel offset, style and
+/// variation coordinates. Synthetic oblique is a shear of the glyph
+/// transform, which leaves outlines as is.
+#[derive(Default)]
+pub(crate) struct OutlineCache<P = TtfGlyphProvider> {
+    pub(crate) provider: P,
+    /// Cached glyphs, grouped by normalized variation coordinates.
+    glyphs: HashMap<Vec<NormalizedCoord>, HashMap<GlyphKey, GlyphEntry>>,
+    /// Scratch encoding the provider writes outlines to.
+    scratch: Encoding,
+    /// Scratch buffer for the commands of a single glyph.
+    commands: Vec<OutlineCommand>,
+    epoch: u64,
+}
+
+impl<P: GlyphProvider> OutlineCache<P> {
+    pub(crate) fn new(provider: P) -> Self {
+        Self {
+            provider,
+            glyphs: HashMap::new(),
+            scratch: Encoding::new(),
+            commands: Vec::new(),
+            epoch: 0,
+        }
+    }
+
+    /// Evicts glyphs that haven't been used for a while.
+    pub(crate) fn maintain(&mut self) {
+        self.epoch += 1;
+        let epoch = self.epoch;
+        self.glyphs.retain(|_, glyphs| {
+            glyphs.retain(|_, entry| entry.epoch + MAX_ENTRY_AGE > epoch);
+            !glyphs.is_empty()
+        });
+    }
+
+    /// Creates a session for encoding glyphs of `font` at `size` pixels per
+    /// em with the given variation coordinates and style, expanded by
+    /// `embolden` pixels.
+    ///
+    /// Glyphs the provider has no outline for produce empty outlines, so the
+    /// glyph run still resolves to a valid (invisible) draw object.
+    pub(crate) fn session<'a>(
+        &'a mut self,
+        font: &'a Font,
+        coords: &'a [NormalizedCoord],
+        size: f32,
+        hint: bool,
+        embolden: f32,
+        style: &Style,
+    ) -> OutlineSession<'a, P> {
+        // Zero width strokes fall back to a fill so that the glyph remains
+        // visible.
+        let style = match style {
+            Style::Fill(fill) => crate::Style::from_fill(*fill),
+            Style::Stroke(stroke) => crate::Style::from_stroke(stroke)
+                .unwrap_or_else(|| crate::Style::from_fill(Fill::NonZero)),
+        };
+        let key = GlyphKey {
+            font_id: font.data.id(),
+            font_index: font.index,
+            glyph_id: 0,
+            font_size_bits: size.to_bits(),
+            hint,
+            embolden_bits: embolden.to_bits(),
+            x_offset_bits: 0,
+            style: bytemuck::cast(style),
+        };
+        if !self.glyphs.contains_key(coords) {
+            self.glyphs.insert(coords.to_vec(), HashMap::new());
+        }
+        OutlineSession {
+            provider: &mut self.provider,
+            glyphs: self.glyphs.get_mut(coords).unwrap(),
+            scratch: &mut self.scratch,
+            commands: &mut self.commands,
+            font,
+            coords,
+            key,
+            size,
+            style,
+            epoch: self.epoch,
+        }
+    }
+}
+
+/// Encodes glyphs of a single font, size and style.
+pub(crate) struct OutlineSession<'a, P> {
+    provider: &'a mut P,
+    glyphs: &'a mut HashMap<GlyphKey, GlyphEntry>,
+    scratch: &'a mut Encoding,
+    commands: &'a mut Vec<OutlineCommand>,
+    font: &'a Font,
+    coords: &'a [NormalizedCoord],
+    key: GlyphKey,
+    size: f32,
+    style: crate::Style,
+    epoch: u64,
+}
+
+impl<P: GlyphProvider> OutlineSession<'_, P> {
+    /// Returns the encoded outline of a glyph along with its stream sizes.
+    ///
+    /// The outline is in pixels with the y axis pointing up and contains the
+    /// style and path segments but no path marker. Glyphs without an outline,
+    /// such as spaces or unknown identifiers, have no path segments.
+    pub(crate) fn get(&mut self, glyph_id: u32) -> (Arc<Encoding>, StreamOffsets) {
+        self.get_offset(glyph_id, 0.0)
+    }
+
+    /// Returns the encoded outline of a glyph moved right by `x_offset`
+    /// pixels.
+    ///
+    /// Offsets are used for subpixel positioning, where the fractional part
+    /// of the glyph position is quantized and baked into the outline.
+    pub(crate) fn get_offset(
+        &mut self,
+        glyph_id: u32,
+        x_offset: f32,
+    ) -> (Arc<Encoding>, StreamOffsets) {
+        let key = GlyphKey {
+            glyph_id,
+            x_offset_bits: x_offset.to_bits(),
+            ..self.key
+        };
+        if let Some(entry) = self.glyphs.get_mut(&key) {
+            entry.epoch = self.epoch;
+            return (entry.encoding.clone(), entry.stream_sizes);
+        }
+        let mut encoding = Encoding::new();
+        encoding.encode_style(self.style);
+        let mut path = encoding.encode_path(self.style.is_fill());
+        let embolden = f32::from_bits(self.key.embolden_bits);
+        if embolden == 0.0 && x_offset == 0.0 {
+            self.provider.outline(
+                self.font,
+                glyph_id,
+                self.size,
+                self.coords,
+                self.key.hint,
+                &mut path,
+            );
+        } else {
+            // Synthetic bold and subpixel offsets need the points of the
+            // outline, so it takes a detour through the scratch encoding.
+            self.scratch.reset();
+            let mut scratch_path = self.scratch.encode_path(true);
+            self.provider.outline(
+                self.font,
+                glyph_id,
+                self.size,
+                self.coords,
+                self.key.hint,
+                &mut scratch_path,
+            );
+            scratch_path.finish(true);
+            self.commands.clear();
+            if let Some((outline, ..)) = self.scratch.decode_paths().next() {
+                self.commands
+                    .extend(outline.elements().iter().map(|el| match *el {
+                        PathEl::MoveTo(p) => OutlineCommand::MoveTo(p.x as f32, p.y as f32),
+                        PathEl::LineTo(p) => OutlineCommand::LineTo(p.x as f32, p.y as f32),
+                        PathEl::QuadTo(p1, p) => {
+                            OutlineCommand::QuadTo(p1.x as f32, p1.y as f32, p.x as f32, p.y as f32)
+                        }
+                        PathEl::CurveTo(p1, p2, p) => OutlineCommand::CubicTo(
+                            p1.x as f32,
+                            p1.y as f32,
+                            p2.x as f32,
+                            p2.y as f32,
+                            p.x as f32,
+                            p.y as f32,
+                        ),
+                        PathEl::ClosePath => OutlineCommand::Close,
+                    }));
+            }
+            if embolden != 0.0 {
+                embolden_outline(self.commands, embolden);
+                // Emboldening moves the edges off the pixel grid again.
+                if self.key.hint {
+                    hint_outline(self.commands);
+                }
+            }
+            encode_commands(self.commands, &mut path, x_offset);
+        }
+        // The resolver inserts a single path marker for the whole run.
+        path.finish(false);
+        let stream_sizes = encoding.stream_offsets();
+        let encoding = Arc::new(encoding);
+        self.glyphs.insert(
+            key,
+            GlyphEntry {
+                encoding: encoding.clone(),
+                stream_sizes,
+                epoch: self.epoch,
+            },
+        );
+        (encoding, stream_sizes)
+    }
+}
+
+/// Applies normalized variation coordinates to the axes of a face, in axis
+/// order.
+///
+/// `ttf_parser` only accepts user space values, which it normalizes and maps
+/// through the `avar` table of the font. The coordinates are final, so they
+/// are mapped back through the `avar` table and the axis ranges first.
+/// Missing coordinates keep the axis default and extra coordinates are
+/// ignored.
+pub(crate) fn set_variations(face: &mut ttf_parser::Face<'_>, coords: &[NormalizedCoord]) {
+    if coords.iter().all(|coord| *coord == 0) {
+        return;
+    }
+    let avar = face.tables().avar;
+    let mut segment_maps = avar.map(|avar| avar.segment_maps.into_iter());
+    for (axis, coord) in face.variation_axes().into_iter().zip(coords) {
+        let coord = match segment_maps.as_mut().and_then(Iterator::next) {
+            Some(map) => unmap_coordinate(map, *coord),
+            None => *coord,
+        };
+        // 2.14 fixed point. `ttf_parser` truncates the value it normalizes,
+        // so this aims for the middle of the unit.
+        let coord = (f32::from(coord) + 0.5 * f32::from(coord.signum())) / 16384.0;
+        let value = if coord < 0.0 {
+            axis.def_value + coord * (axis.def_value - axis.min_value)
+        } else {
+            axis.def_value + coord * (axis.max_value - axis.def_value)
+        };
+        face.set_variation(axis.tag, value);
+    }
+}
+
+/// Maps a coordinate through the segment map of an axis in reverse.
+///
+/// This inverts the piecewise linear mapping applied by `ttf_parser`, which
+/// is monotonic for valid fonts.
+fn unmap_coordinate(map: LazyArray16<'_, AxisValueMap>, coord: i16) -> i16 {
+    let coord = i32::from(coord);
+    let clamp = |value: i32| value.clamp(-16384, 16384) as i16;
+    let mut prev: Option<(i32, i32)> = None;
+    for record in map {
+        let from = i32::from(record.from_coordinate);
+        let to = i32::from(record.to_coordinate);
+        if coord <= to {
+            return clamp(match prev {
+                Some((prev_from, prev_to)) if prev_to < to => {
+                    let denom = to - prev_to;
+                    prev_from + ((from - prev_from) * (coord - prev_to) + denom / 2) / denom
+                }
+                _ => coord - to + from,
+            });
+        }
+        prev = Some((from, to));
+    }
+    clamp(prev.map_or(coord, |(from, to)| coord - to + from))
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::kurbo::{Rect, Shape, Stroke};
+    use peniko::{Blob, Fill, Font, Style};
+
+    use std::sync::Arc;
+
+    use super::{MAX_ENTRY_AGE, OutlineCache, OutlineCommand, OutlineSession, hint_outline};
+    use crate::PathTag;
+    use crate::provider::TtfGlyphProvider;
+    use crate::test_font::TestFont;
+
+    /// Returns the bounding box of the outline of a glyph moved right by
+    /// `x_offset` pixels.
+    fn outline_bounds(
+        session: &mut OutlineSession<'_, TtfGlyphProvider>,
+        glyph_id: u32,
+        x_offset: f32,
+    ) -> Rect {
+        let (glyph, _) = session.get_offset(glyph_id, x_offset);
+        let mut terminated = (*glyph).clone();
+        terminated.path_tags.push(PathTag::PATH);
+        let (path, ..) = terminated.decode_paths().next().unwrap();
+        path.bounding_box()
+    }
+
+    #[test]
+    fn glyph_outlines() {
+        let font = TestFont::new(1000).build();
+        let font = Font::new(Blob::from(font), 0);
+        let mut cache: OutlineCache = OutlineCache::default();
+        let style = Style::Fill(Fill::EvenOdd);
+        let mut session = cache.session(&font, &[], 20.0, false, 0.0, &style);
+        let (square, sizes) = session.get(TestFont::SQUARE);
+        assert_eq!(sizes.styles, 1);
+        assert_eq!(sizes.path_tags, square.path_tags.len());
+        assert!(!square.path_tags.contains(&PathTag::PATH));
+        // 100..600 by 0..500 font units at 20 pixels per 1000 units.
+        assert_eq!(
+            outline_bounds(&mut session, TestFont::SQUARE, 0.0),
+            Rect::new(2.0, 0.0, 12.0, 10.0)
+        );
+        // Glyphs without an outline only have a style.
+        for id in [TestFont::SPACE, 1000, 70_000] {
+            let (empty, sizes) = session.get(id);
+            assert_eq!(sizes.path_tags, 1);
+            assert_eq!(empty.styles.len(), 1);
+        }
+        let stroke = Style::Stroke(Stroke::new(2.0));
+        let (outline, _) = cache
+            .session(&font, &[], 20.0, false, 0.0, &stroke)
+            .get(TestFont::SQUARE);
+        assert!(!outline.styles[0].is_fill());
+        // Unparseable fonts produce empty outlines.
+        let invalid = Font::new(Blob::from(vec![0_u8; 8]), 0);
+        let (empty, _) = cache
+            .session(&invalid, &[], 20.0, false, 0.0, &style)
+            .get(TestFont::SQUARE);
+        assert!(empty.path_data.is_empty());
+    }
+
+    #[test]
+    fn glyph_cache() {
+        let font = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let mut cache: OutlineCache = OutlineCache::default();
+        let fill = Style::Fill(Fill::NonZero);
+        let (square, _) = cache
+            .session(&font, &[], 20.0, false, 0.0, &fill)
+            .get(TestFont::SQUARE);
+        let (cached, _) = cache
+            .session(&font, &[], 20.0, false, 0.0, &fill)
+            .get(TestFont::SQUARE);
+        assert!(Arc::ptr_eq(&square, &cached));
+        // Every part of the key selects a separate entry.
+        let stroke = Style::Stroke(Stroke::new(1.0));
+        let (triangle, _) = cache
+            .session(&font, &[], 20.0, false, 0.0, &fill)
+            .get(TestFont::TRIANGLE);
+        let (resized, _) = cache
+            .session(&font, &[], 10.0, false, 0.0, &fill)
+            .get(TestFont::SQUARE);
+        let (hinted, _) = cache
+            .session(&font, &[], 20.0, true, 0.0, &fill)
+            .get(TestFont::SQUARE);
+        let (stroked, _) = cache
+            .session(&font, &[], 20.0, false, 0.0, &stroke)
+            .get(TestFont::SQUARE);
+        let (bold, _) = cache
+            .session(&font, &[], 20.0, false, 0.5, &fill)
+            .get(TestFont::SQUARE);
+        let (varied, _) = cache
+            .session(&font, &[1], 20.0, false, 0.0, &fill)
+            .get(TestFont::SQUARE);
+        let other = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let (other, _) = cache
+            .session(&other, &[], 20.0, false, 0.0, &fill)
+            .get(TestFont::SQUARE);
+        for glyph in [triangle, resized, hinted, bold, stroked, varied, other] {
+            assert!(!Arc::ptr_eq(&square, &glyph));
+        }
+        // Glyphs in use are retained, others are evicted.
+        for _ in 0..MAX_ENTRY_AGE {
+            cache.maintain();
+            cache
+                .session(&font, &[], 20.0, false, 0.0, &fill)
+                .get(TestFont::SQUARE);
+        }
+        let mut session = cache.session(&font, &[], 20.0, false, 0.0, &fill);
+        assert!(Arc::ptr_eq(&square, &session.get(TestFont::SQUARE).0));
+        assert_eq!(session.glyphs.len(), 1);
+        assert_eq!(cache.glyphs.len(), 1);
+    }
+
+    #[test]
+    fn hinting() {
+        let font = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let mut cache: OutlineCache = OutlineCache::default();
+        let style = Style::Fill(Fill::NonZero);
+        let mut bounds = |hint: bool, x_offset: f32| {
+            let mut session = cache.session(&font, &[], 15.0, hint, 0.0, &style);
+            outline_bounds(&mut session, TestFont::SQUARE, x_offset)
+        };
+        // 100..600 by 0..500 font units at 15 pixels per 1000 units.
+        assert_eq!(bounds(false, 0.0), Rect::new(1.5, 0.0, 9.0, 7.5));
+        // Only the horizontal edges snap to the pixel grid.
+        assert_eq!(bounds(true, 0.0), Rect::new(1.5, 0.0, 9.0, 8.0));
+        assert_eq!(bounds(true, 0.25), Rect::new(1.75, 0.0, 9.25, 8.0));
+    }
+
+    #[test]
+    fn synthetic_bold() {
+        let font = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let mut cache: OutlineCache = OutlineCache::default();
+        let style = Style::Fill(Fill::NonZero);
+        let mut bounds = |glyph_id: u32, embolden: f32| {
+            let mut session = cache.session(&font, &[], 1000.0, false, embolden, &style);
+            outline_bounds(&mut session, glyph_id, 0.0)
+        };
+        // Straight edges move outward by the full strength.
+        assert_eq!(
+            bounds(TestFont::SQUARE, 10.0),
+            Rect::new(90.0, -10.0, 610.0, 510.0)
+        );
+        let triangle = bounds(TestFont::TRIANGLE, 0.0);
+        let bold = bounds(TestFont::TRIANGLE, 10.0);
+        assert!(bold.contains_rect(triangle.inset(-9.0)));
+    }
+
+    #[test]
+    fn hint_interpolation() {
+        let mut commands = vec![
+            OutlineCommand::MoveTo(0.0, 0.4),
+            OutlineCommand::LineTo(4.0, 0.4),
+            OutlineCommand::QuadTo(4.0, 2.0, 2.0, 2.0),
+            OutlineCommand::QuadTo(0.0, 2.0, 0.0, 1.0),
+            OutlineCommand::Close,
+        ];
+        hint_outline(&mut commands);
+        let ys = commands
+            .iter()
+            .flat_map(|command| match *command {
+                OutlineCommand::MoveTo(_, y) | OutlineCommand::LineTo(_, y) => vec![y],
+                OutlineCommand::QuadTo(_, y1, _, y) => vec![y1, y],
+                _ => vec![],
+            })
+            .collect::<Vec<_>>();
+        // The edges at 0.4 and 2.0 become 0.0 and 2.0, the point between
+        // them moves proportionally.
+        assert_eq!(ys, [0.0, 0.0, 2.0, 2.0, 2.0, 0.75]);
+    }
+
+    #[test]
+    fn variations() {
+        let font = TestFont::new(1000).with_weight_axis().build();
+        let font = Font::new(Blob::from(font), 0);
+        let mut cache: OutlineCache = OutlineCache::default();
+        let style = Style::Fill(Fill::NonZero);
+        let mut right_edge = |coords: &[i16]| {
+            let mut session = cache.session(&font, coords, 1000.0, false, 0.0, &style);
+            outline_bounds(&mut session, TestFont::SQUARE, 0.0).x1
+        };
+        assert_eq!(right_edge(&[]), 600.0);
+        assert_eq!(right_edge(&[0]), 600.0);
+        // The single tuple only applies to weights above the default.
+        assert_eq!(right_edge(&[-0x4000]), 600.0);
+        assert_eq!(right_edge(&[0x2000]), 700.0);
+        assert_eq!(right_edge(&[0x4000]), 800.0);
+        // Coordinates beyond the axis count are ignored.
+        assert_eq!(right_edge(&[0x4000, 0x4000]), 800.0);
+    }
+
+    #[test]
+    fn avar_variations() {
+        let font = TestFont::new(1000).with_weight_avar().build();
+        let font = Font::new(Blob::from(font), 0);
+        let mut cache: OutlineCache = OutlineCache::default();
+        let style = Style::Fill(Fill::NonZero);
+        let mut right_edge = |coords: &[i16]| {
+            let mut session = cache.session(&font, coords, 1000.0, false, 0.0, &style);
+            outline_bounds(&mut session, TestFont::SQUARE, 0.0).x1
+        };
+        // The coordinates already have the segment map applied, so 0.25
+        // is a quarter of the way to the maximum weight.
+        assert_eq!(right_edge(&[0x1000]), 650.0);
+        assert_eq!(right_edge(&[0x4000]), 800.0);
+        assert_eq!(right_edge(&[-0x4000]), 600.0);
+    }
+}
@@ -109,8 +109,8 @@ impl Style {
         })
     }
 
-    #[cfg(test)]
-    fn fill(self) -> Option<Fill> {
+    /// Returns the fill rule, or `None` if this is a stroke style.
+    pub fn fill(self) -> Option<Fill> {
         if self.is_fill() {
             Some(
                 if (self.flags_and_miter_limit & Self::FLAGS_FILL_BIT) == 0 {
@@ -124,16 +124,16 @@ impl Style {
         }
     }
 
-    #[cfg(test)]
-    fn stroke_width(self) -> Option<f64> {
+    /// Returns the stroke width, or `None` if this is a fill style.
+    pub fn stroke_width(self) -> Option<f64> {
         if self.is_fill() {
             return None;
         }
         Some(self.line_width.into())
     }
 
-    #[cfg(test)]
-    fn stroke_join(self) -> Option<Join> {
+    /// Returns the stroke join, or `None` if this is a fill style.
+    pub fn stroke_join(self) -> Option<Join> {
         if self.is_fill() {
             return None;
         }
@@ -146,8 +146,8 @@ impl Style {
         })
     }
 
-    #[cfg(test)]
-    fn stroke_start_cap(self) -> Option<Cap> {
+    /// Returns the stroke start cap, or `None` if this is a fill style.
+    pub fn stroke_start_cap(self) -> Option<Cap> {
         if self.is_fill() {
             return None;
         }
@@ -160,8 +160,8 @@ impl Style {
         })
     }
 
-    #[cfg(test)]
-    fn stroke_end_cap(self) -> Option<Cap> {
+    /// Returns the stroke end cap, or `None` if this is a fill style.
+    pub fn stroke_end_cap(self) -> Option<Cap> {
         if self.is_fill() {
             return None;
         }
@@ -174,18 +174,43 @@ impl Style {
         })
     }
 
-    #[cfg(test)]
-    fn stroke_miter_limit(self) -> Option<u16> {
+    /// Returns the binary16 encoded miter limit, or `None` if this is a fill
+    /// style.
+    pub fn stroke_miter_limit(self) -> Option<u16> {
         if self.is_fill() {
             return None;
         }
         Some((self.flags_and_miter_limit & Self::MITER_LIMIT_MASK) as u16)
     }
 
-    #[cfg(test)]
-    fn is_fill(self) -> bool {
+    /// Returns `true` if this is a fill style.
+    pub fn is_fill(self) -> bool {
         (self.flags_and_miter_limit & Self::FLAGS_STYLE_BIT) == 0
     }
+
+    /// Reconstructs the stroke, or returns `None` if this is a fill style.
+    ///
+    /// The miter limit is only stored with half precision, so it may differ
+    /// slightly from the value the style was created with.
+    pub fn stroke(self) -> Option<Stroke> {
+        let miter_limit = crate::math::f16_to_f32(self.stroke_miter_limit()?);
+        Some(
+            Stroke::new(self.stroke_width()?)
+                .with_join(self.stroke_join()?)
+                .with_start_cap(self.stroke_start_cap()?)
+                .with_end_cap(self.stroke_end_cap()?)
+                .with_miter_limit(miter_limit.into()),
+        )
+    }
+}
+
+impl From<Style> for peniko::Style {
+    fn from(style: Style) -> Self {
+        match style.fill() {
+            Some(fill) => Self::Fill(fill),
+            None => Self::Stroke(style.stroke().unwrap_or_default()),
+        }
+    }
 }
 
 /// Line segment (after flattening, before tiling).
@@ -313,6 +338,20 @@ impl PathTag {
     pub fn path_segment_type(self) -> PathSegmentType {
         PathSegmentType(self.0 & Self::SEGMENT_MASK)
     }
+
+    /// Returns the number of `u32` words this tag consumes from the path data
+    /// stream.
+    ///
+    /// A segment that ends a subpath also accounts for the start point of the
+    /// following subpath.
+    pub fn data_size(self) -> usize {
+        let n_points = self.path_segment_type().0 as usize + self.is_subpath_end() as usize;
+        if self.is_f32() {
+            n_points * 2
+        } else {
+            n_points
+        }
+    }
 }
 
 /// Monoid for the path tag stream.
@@ -433,6 +472,9 @@ pub struct PathEncoder<'a> {
     state: PathState,
     n_encoded_segments: u32,
     is_fill: bool,
+    i16_tolerance: Option<f32>,
+    subpath_tag_start: usize,
+    subpath_data_start: usize,
 }
 
 #[derive(PartialEq)]
@@ -485,6 +527,8 @@ impl<'a> PathEncoder<'a> {
         n_paths: &'a mut u32,
         is_fill: bool,
     ) -> Self {
+        let subpath_tag_start = tags.len();
+        let subpath_data_start = data.len();
         Self {
             tags,
             data,
@@ -495,9 +539,27 @@ impl<'a> PathEncoder<'a> {
             state: PathState::Start,
             n_encoded_segments: 0,
             is_fill,
+            i16_tolerance: None,
+            subpath_tag_start,
+            subpath_data_start,
         }
     }
 
+    /// Enables the compact segment encoding.
+    ///
+    /// When set, each completed subpath whose coordinates are all within
+    /// `tolerance` of an integer in the `i16` range is stored with the
+    /// `*_I16` tags, which use half the path data of the `*_F32` tags. Other
+    /// subpaths fall back to `f32`. A tolerance of zero only compacts
+    /// subpaths that are exactly representable.
+    ///
+    /// The format can't change within a subpath because each segment reads
+    /// its start point in its own format.
+    pub fn with_i16_tolerance(mut self, tolerance: Option<f32>) -> Self {
+        self.i16_tolerance = tolerance;
+        self
+    }
+
     /// Encodes a move, starting a new subpath.
     pub fn move_to(&mut self, x: f32, y: f32) {
         if self.is_fill {
@@ -515,6 +577,11 @@ impl<'a> PathEncoder<'a> {
             if let Some(tag) = self.tags.last_mut() {
                 tag.set_subpath_end();
             }
+            self.compact_subpath();
+        }
+        if self.state != PathState::MoveTo {
+            self.subpath_tag_start = self.tags.len();
+            self.subpath_data_start = self.data.len();
         }
         self.first_point = buf;
         self.data.extend_from_slice(bytes);
@@ -607,14 +674,16 @@ impl<'a> PathEncoder<'a> {
         self.n_encoded_segments += 1;
     }
 
-    /// Encodes an empty path for clipping boundaries using proper GPU path encoding.
+    /// Encodes an empty path for clipping boundaries.
+    ///
+    /// This emits a single degenerate line segment so that the path still
+    /// occupies a path slot for the associated draw object.
     pub(crate) fn empty_path(&mut self) -> u32 {
-        // Create empty path using proper PathEncoder state management
-        // This creates a degenerate path with no drawable area for clipping operations
-        let start_segments = self.n_encoded_segments;
-        self.move_to(0.0, 0.0);
-        // Return the number of segments added (move_to adds 1 segment)
-        self.n_encoded_segments - start_segments
+        let coords = [0.0_f32, 0., 0., 0.];
+        self.data.extend_from_slice(bytemuck::cast_slice(&coords));
+        self.tags.push(PathTag::LINE_TO_F32);
+        self.n_encoded_segments += 1;
+        1
     }
 
     /// Closes the current subpath.
@@ -649,6 +718,7 @@ impl<'a> PathEncoder<'a> {
         if let Some(tag) = self.tags.last_mut() {
             tag.set_subpath_end();
         }
+        self.compact_subpath();
         self.state = PathState::Start;
     }
 
@@ -700,6 +770,9 @@ impl<'a> PathEncoder<'a> {
             if let Some(tag) = self.tags.last_mut() {
                 tag.set_subpath_end();
             }
+            if self.state == PathState::NonemptySubpath {
+                self.compact_subpath();
+            }
             *self.n_segments += self.n_encoded_segments;
             if insert_path_marker {
                 self.tags.push(PathTag::PATH);
@@ -709,6 +782,47 @@ impl<'a> PathEncoder<'a> {
         self.n_encoded_segments
     }
 
+    /// Converts the just completed subpath to `i16` coordinates if the
+    /// compact encoding is enabled and all of its points allow it.
+    fn compact_subpath(&mut self) {
+        let Some(tolerance) = self.i16_tolerance else {
+            return;
+        };
+        let data = &self.data[self.subpath_data_start..];
+        if data.is_empty() || self.tags[self.subpath_tag_start..].is_empty() {
+            return;
+        }
+        let mut packed = Vec::with_capacity(data.len() / 2);
+        let mut prev = None;
+        for point in data.chunks_exact(2) {
+            let [x, y] = [point[0], point[1]].map(f32::from_bits);
+            let [qx, qy] = [x, y].map(f32::round);
+            let in_range = |v: f32| (i16::MIN as f32..=i16::MAX as f32).contains(&v);
+            if (x - qx).abs() > tolerance
+                || (y - qy).abs() > tolerance
+                || !in_range(qx)
+                || !in_range(qy)
+            {
+                return;
+            }
+            // Rounding must not collapse distinct points, as that would
+            // introduce the zero length segments and tangents that the
+            // encoder otherwise removes.
+            if prev.is_some_and(|(prev, prev_q)| prev != [x, y] && prev_q == [qx, qy]) {
+                return;
+            }
+            prev = Some(([x, y], [qx, qy]));
+            packed.push((qx as i16 as u16 as u32) | ((qy as i16 as u16 as u32) << 16));
+        }
+        self.data.truncate(self.subpath_data_start);
+        self.data.extend_from_slice(&packed);
+        for tag in &mut self.tags[self.subpath_tag_start..] {
+            if tag.is_path_segment() {
+                tag.0 &= !PathTag::F32_BIT;
+            }
+        }
+    }
+
     fn insert_stroke_cap_marker_segment(&mut self, is_closed: bool) {
         assert!(!self.is_fill);
         assert!(self.state == PathState::NonemptySubpath);
@@ -852,8 +966,117 @@ mod tests {
                     assert_eq!(Some(stroke.start_cap), encoded.stroke_start_cap());
                     assert_eq!(Some(stroke.end_cap), encoded.stroke_end_cap());
                     assert_eq!(Some(0), encoded.stroke_miter_limit());
+                    assert_eq!(Some(stroke.width), encoded.stroke().map(|s| s.width));
                 }
             }
         }
     }
+
+    fn compact_encoding(tolerance: f32, build: impl Fn(&mut crate::Encoding)) -> crate::Encoding {
+        let mut encoding = crate::Encoding::new();
+        encoding.i16_tolerance = Some(tolerance);
+        encoding.encode_transform(crate::Transform::IDENTITY);
+        build(&mut encoding);
+        assert_eq!(encoding.validate(), Ok(()));
+        encoding
+    }
+
+    #[test]
+    fn compact_subpaths() {
+        use peniko::kurbo::{BezPath, Rect};
+
+        let mut path = BezPath::new();
+        path.move_to((0.0, 0.0));
+        path.quad_to((10.0, -20.0), (30.0, 0.0));
+        path.close_path();
+        path.move_to((0.5, 0.0));
+        path.line_to((10.0, 0.0));
+        path.line_to((10.0, 10.0));
+        let build = |encoding: &mut crate::Encoding| {
+            encoding.encode_fill_style(Fill::NonZero);
+            encoding.encode_path_elements(path.iter(), true);
+            encoding.encode_color(peniko::Color::WHITE);
+            assert!(encoding.encode_stroke_style(&Stroke::new(2.0)));
+            encoding.encode_shape(&Rect::new(-3.0, 0.0, 3.0, 6.0), false);
+            encoding.encode_color(peniko::Color::WHITE);
+        };
+        let encoding = compact_encoding(0.0, build);
+        let tags: Vec<_> = encoding
+            .path_tags
+            .iter()
+            .filter(|tag| tag.is_path_segment())
+            .map(|tag| tag.is_f32())
+            .collect();
+        // The first subpath is compacted, the second one has a fractional
+        // coordinate and the rectangle stroke is compacted again.
+        assert_eq!(
+            tags,
+            [[false; 2].as_slice(), &[true; 3], &[false; 5]].concat()
+        );
+        assert_eq!(encoding.path_data.len(), 4 + 8 + 6);
+        let reference = {
+            let mut encoding = crate::Encoding::new();
+            encoding.encode_transform(crate::Transform::IDENTITY);
+            build(&mut encoding);
+            encoding
+        };
+        let decoded: Vec<_> = encoding.decode_paths().map(|path| path.0).collect();
+        let expected: Vec<_> = reference.decode_paths().map(|path| path.0).collect();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn compact_tolerance() {
+        use peniko::kurbo::Rect;
+
+        let rect = |encoding: &mut crate::Encoding| {
+            encoding.encode_fill_style(Fill::NonZero);
+            encoding.encode_shape(&Rect::new(0.1, 0.0, 2.0, 40_000.0), true);
+            encoding.encode_color(peniko::Color::WHITE);
+        };
+        assert!(compact_encoding(0.0, rect).path_tags[2].is_f32());
+        // Out of range for i16.
+        assert!(compact_encoding(0.25, rect).path_tags[2].is_f32());
+        let encoding = compact_encoding(0.25, |encoding| {
+            encoding.encode_fill_style(Fill::NonZero);
+            encoding.encode_shape(&Rect::new(0.1, -0.2, 2.0, 3.9), true);
+            encoding.encode_color(peniko::Color::WHITE);
+        });
+        assert!(!encoding.path_tags[2].is_f32());
+        assert_eq!(encoding.path_data[..2], [0, 2]);
+        // Points must not collapse onto each other.
+        let encoding = compact_encoding(0.5, |encoding| {
+            encoding.encode_fill_style(Fill::NonZero);
+            encoding.encode_shape(&Rect::new(0.0, 0.0, 0.25, 0.25), true);
+            encoding.encode_color(peniko::Color::WHITE);
+        });
+        assert!(encoding.path_tags[2].is_f32());
+    }
+
+    #[test]
+    fn compact_append_and_resolve() {
+        use peniko::kurbo::Rect;
+
+        let compact = compact_encoding(0.0, |encoding| {
+            encoding.encode_fill_style(Fill::NonZero);
+            encoding.encode_shape(&Rect::new(0.0, 0.0, 8.0, 8.0), true);
+            encoding.encode_color(peniko::Color::WHITE);
+        });
+        let mut encoding = crate::Encoding::new();
+        encoding.encode_transform(crate::Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&Rect::new(0.5, 0.5, 1.0, 1.0), true);
+        encoding.encode_color(peniko::Color::WHITE);
+        let f32_len = encoding.path_data.len();
+        encoding.append(&compact, &Some(crate::Transform::IDENTITY));
+        assert_eq!(encoding.validate(), Ok(()));
+        assert_eq!(encoding.path_data[f32_len..], compact.path_data[..]);
+        let paths: Vec<_> = encoding.decode_paths().map(|path| path.0).collect();
+        assert_eq!(paths[1].bounding_box(), Rect::new(0.0, 0.0, 8.0, 8.0));
+
+        let mut packed = Vec::new();
+        let layout = crate::resolve_solid_paths_only(&encoding, &mut packed);
+        let data: &[u32] = bytemuck::cast_slice(layout.path_data(&packed));
+        assert_eq!(data[..encoding.path_data.len()], encoding.path_data[..]);
+    }
 }
@@ -0,0 +1,220 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Pixel formats of images and their conversion to the atlas format.
+
+use std::fmt;
+
+use peniko::{Blob, Image, ImageFormat};
+
+use crate::math::f16_to_f32;
+
+/// Layout of the pixels of an image.
+///
+/// Multi-byte channels are in native byte order. Images use the format of
+/// their [`ImageFormat`] unless a format is set with
+/// [`Resolver::set_image_format`](crate::Resolver::set_image_format).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 8-bit red, green, blue and alpha channels.
+    Rgba8,
+    /// 8-bit blue, green, red and alpha channels.
+    Bgra8,
+    /// 16-bit unsigned normalized red, green, blue and alpha channels.
+    Rgba16,
+    /// 16-bit floating point red, green, blue and alpha channels, which are
+    /// clamped to the unit range.
+    Rgba16Float,
+    /// 8-bit alpha channel of a white image.
+    Alpha8,
+    /// 8-bit gray channel of an opaque image.
+    Gray8,
+}
+
+impl PixelFormat {
+    /// Returns the size of a pixel in bytes.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            Self::Rgba8 | Self::Bgra8 => 4,
+            Self::Rgba16 | Self::Rgba16Float => 8,
+            Self::Alpha8 | Self::Gray8 => 1,
+        }
+    }
+}
+
+/// Whether the color channels of an image are multiplied by its alpha.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum AlphaType {
+    /// Color channels are independent of alpha.
+    #[default]
+    Straight,
+    /// Color channels are premultiplied by alpha.
+    Premultiplied,
+}
+
+/// Errors that can occur when converting an image to the atlas format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImageError {
+    /// The format of the image can't be converted.
+    UnsupportedFormat(ImageFormat),
+    /// The image data is shorter than its format and dimensions require.
+    Truncated {
+        /// Required size in bytes.
+        expected: usize,
+        /// Size of the image data in bytes.
+        actual: usize,
+    },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => write!(f, "unsupported image format {format:?}"),
+            Self::Truncated { expected, actual } => write!(
+                f,
+                "image data is truncated ({actual} bytes, expected {expected})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Converts an image to premultiplied RGBA8, the format of the atlas.
+///
+/// The pixels are read in `format`, if set, and in the format of the image
+/// otherwise. Everything but the data of the image is kept.
+pub(crate) fn convert_image(
+    image: &Image,
+    format: Option<(PixelFormat, AlphaType)>,
+) -> Result<Image, ImageError> {
+    let (format, alpha_type) = match (format, image.format) {
+        (Some(format), _) => format,
+        (None, ImageFormat::Rgba8) => (PixelFormat::Rgba8, AlphaType::Straight),
+        (None, format) => return Err(ImageError::UnsupportedFormat(format)),
+    };
+    let n_pixels = image.width as usize * image.height as usize;
+    let expected = n_pixels * format.bytes_per_pixel();
+    let data = image.data.data();
+    let Some(data) = data.get(..expected) else {
+        return Err(ImageError::Truncated {
+            expected,
+            actual: data.len(),
+        });
+    };
+    let mut pixels = Vec::with_capacity(n_pixels * 4);
+    let mut push = |rgba: [u8; 4]| {
+        let [r, g, b, a] = rgba;
+        if alpha_type == AlphaType::Straight {
+            let premultiply = |c: u8| ((u16::from(c) * u16::from(a) + 127) / 255) as u8;
+            pixels.extend([premultiply(r), premultiply(g), premultiply(b), a]);
+        } else {
+            pixels.extend(rgba);
+        }
+    };
+    match format {
+        PixelFormat::Rgba8 => data
+            .chunks_exact(4)
+            .for_each(|pixel| push([pixel[0], pixel[1], pixel[2], pixel[3]])),
+        PixelFormat::Bgra8 => data
+            .chunks_exact(4)
+            .for_each(|pixel| push([pixel[2], pixel[1], pixel[0], pixel[3]])),
+        PixelFormat::Rgba16 | PixelFormat::Rgba16Float => {
+            let to_unorm = |bytes: &[u8]| {
+                let bits = u16::from_ne_bytes([bytes[0], bytes[1]]);
+                if format == PixelFormat::Rgba16 {
+                    ((u32::from(bits) * 255 + 32_767) / 65_535) as u8
+                } else {
+                    // NaN saturates to zero.
+                    (f16_to_f32(bits).clamp(0.0, 1.0) * 255.0).round() as u8
+                }
+            };
+            data.chunks_exact(8).for_each(|pixel| {
+                push([
+                    to_unorm(&pixel[0..2]),
+                    to_unorm(&pixel[2..4]),
+                    to_unorm(&pixel[4..6]),
+                    to_unorm(&pixel[6..8]),
+                ]);
+            });
+        }
+        // Both are the same in either alpha type.
+        PixelFormat::Alpha8 => data.iter().for_each(|&a| pixels.extend([a, a, a, a])),
+        PixelFormat::Gray8 => data.iter().for_each(|&g| pixels.extend([g, g, g, 255])),
+    }
+    Ok(Image {
+        data: Blob::from(pixels),
+        format: ImageFormat::Rgba8,
+        ..image.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::{Blob, Image, ImageFormat};
+
+    use super::{AlphaType, ImageError, PixelFormat, convert_image};
+    use crate::math::f32_to_f16;
+
+    fn convert(data: Vec<u8>, format: PixelFormat, alpha_type: AlphaType) -> Vec<u8> {
+        let image = Image::new(Blob::from(data), ImageFormat::Rgba8, 1, 1);
+        let converted = convert_image(&image, Some((format, alpha_type))).unwrap();
+        converted.data.data().to_vec()
+    }
+
+    #[test]
+    fn formats() {
+        use AlphaType::{Premultiplied, Straight};
+        let rgba = vec![255, 128, 0, 128];
+        assert_eq!(
+            convert(rgba.clone(), PixelFormat::Rgba8, Straight),
+            [128, 64, 0, 128]
+        );
+        assert_eq!(
+            convert(rgba, PixelFormat::Rgba8, Premultiplied),
+            [255, 128, 0, 128]
+        );
+        let bgra = vec![0, 128, 255, 128];
+        assert_eq!(
+            convert(bgra, PixelFormat::Bgra8, Straight),
+            [128, 64, 0, 128]
+        );
+        let rgba16 = [65_535_u16, 0, 32_768, 65_535]
+            .into_iter()
+            .flat_map(u16::to_ne_bytes)
+            .collect();
+        assert_eq!(
+            convert(rgba16, PixelFormat::Rgba16, Straight),
+            [255, 0, 128, 255]
+        );
+        let rgba16f = [2.0, -1.0, 0.5, 1.0]
+            .into_iter()
+            .flat_map(|c| f32_to_f16(c).to_ne_bytes())
+            .collect();
+        assert_eq!(
+            convert(rgba16f, PixelFormat::Rgba16Float, Straight),
+            [255, 0, 128, 255]
+        );
+        assert_eq!(convert(vec![64], PixelFormat::Alpha8, Straight), [64; 4]);
+        assert_eq!(
+            convert(vec![64], PixelFormat::Gray8, Straight),
+            [64, 64, 64, 255]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let image = Image::new(Blob::from(vec![0_u8; 7]), ImageFormat::Rgba8, 2, 1);
+        assert_eq!(
+            convert_image(&image, None).err(),
+            Some(ImageError::Truncated {
+                expected: 8,
+                actual: 7
+            })
+        );
+        let format = Some((PixelFormat::Rgba16, AlphaType::Straight));
+        assert!(convert_image(&image, format).is_err());
+        let format = Some((PixelFormat::Gray8, AlphaType::Straight));
+        assert!(convert_image(&image, format).is_ok());
+    }
+}
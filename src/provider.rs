@@ -0,0 +1,270 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Sources of glyph outlines for the resolver.
+
+use peniko::Font;
+use peniko::kurbo::Rect;
+
+use super::{NormalizedCoord, PathEncoder};
+use crate::outline::{OutlineCommand, OutlineCommandCollector, encode_commands, hint_outline};
+
+/// Metrics of a glyph in pixels, with the y axis pointing up.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct GlyphMetrics {
+    /// Horizontal advance.
+    pub advance: f32,
+    /// Bounding box of the outline, or `None` if the glyph has no outline.
+    pub bounds: Option<Rect>,
+}
+
+/// Source of glyph outlines for the [`Resolver`](crate::Resolver).
+///
+/// The resolver requests each outline once per font, glyph, size, hinting
+/// and variation coordinates and caches the result. Synthetic bold,
+/// subpixel offsets and the style of the run are applied on top of the
+/// outline by the resolver.
+pub trait GlyphProvider {
+    /// Encodes the outline of a glyph at `size` pixels per em into `path`.
+    ///
+    /// The outline is in pixels with the y axis pointing up and the origin
+    /// on the baseline. Nothing is encoded for glyphs without an outline. If
+    /// `hint` is true, the outline may be fitted to the pixel grid.
+    fn outline(
+        &mut self,
+        font: &Font,
+        glyph_id: u32,
+        size: f32,
+        coords: &[NormalizedCoord],
+        hint: bool,
+        path: &mut PathEncoder<'_>,
+    );
+
+    /// Returns the metrics of a glyph at `size` pixels per em, if known.
+    ///
+    /// The default implementation knows no metrics.
+    fn metrics(
+        &mut self,
+        _font: &Font,
+        _glyph_id: u32,
+        _size: f32,
+        _coords: &[NormalizedCoord],
+    ) -> Option<GlyphMetrics> {
+        None
+    }
+}
+
+/// Glyph provider that reads outlines and metrics from the font data with
+/// `ttf_parser`.
+///
+/// Outlines come from the `glyf`, `CFF` or `CFF2` tables. `ttf_parser` doesn't
+/// interpret TrueType instructions, so hinted outlines are fitted to the
+/// pixel grid with a light vertical autohinter instead. Fonts that fail to
+/// parse have no outlines.
+///
+/// The most recently used face is kept parsed, so that the glyphs of a run
+/// don't parse the font again.
+#[derive(Debug, Default)]
+pub struct TtfGlyphProvider {
+    /// Scratch buffer for the commands of a single glyph.
+    commands: Vec<OutlineCommand>,
+    faces: FaceCache,
+}
+
+/// Most recently used face.
+#[derive(Debug, Default)]
+struct FaceCache {
+    /// Blob id, index and variation coordinates of the cached face.
+    key: Option<(u64, u32, Vec<NormalizedCoord>)>,
+    face: Option<CachedFace>,
+}
+
+type ParsedFace<'a> = Option<ttf_parser::Face<'a>>;
+
+self_cell::self_cell!(
+    /// Font together with its parsed face, which borrows the font data.
+    struct CachedFace {
+        owner: Font,
+        #[covariant]
+        dependent: ParsedFace,
+    }
+
+    impl {Debug}
+);
+
+impl FaceCache {
+    /// Returns the face of `font` with the variation `coords` applied, or
+    /// `None` if the font fails to parse.
+    fn get(&mut self, font: &Font, coords: &[NormalizedCoord]) -> Option<&ttf_parser::Face<'_>> {
+        let is_cached = self.key.as_ref().is_some_and(|(id, index, cached)| {
+            *id == font.data.id() && *index == font.index && cached == coords
+        });
+        if !is_cached {
+            self.key = Some((font.data.id(), font.index, coords.to_vec()));
+            self.face = Some(CachedFace::new(font.clone(), |font| {
+                parse_face(font, coords)
+            }));
+        }
+        self.face.as_ref()?.borrow_dependent().as_ref()
+    }
+}
+
+impl GlyphProvider for TtfGlyphProvider {
+    fn outline(
+        &mut self,
+        font: &Font,
+        glyph_id: u32,
+        size: f32,
+        coords: &[NormalizedCoord],
+        hint: bool,
+        path: &mut PathEncoder<'_>,
+    ) {
+        let Ok(id) = u16::try_from(glyph_id) else {
+            return;
+        };
+        let Some(face) = self.faces.get(font, coords) else {
+            return;
+        };
+        self.commands.clear();
+        let scale = size / f32::from(face.units_per_em());
+        let mut collector = OutlineCommandCollector::new(scale, &mut self.commands);
+        face.outline_glyph(ttf_parser::GlyphId(id), &mut collector);
+        if hint {
+            hint_outline(&mut self.commands);
+        }
+        encode_commands(&self.commands, path, 0.0);
+    }
+
+    fn metrics(
+        &mut self,
+        font: &Font,
+        glyph_id: u32,
+        size: f32,
+        coords: &[NormalizedCoord],
+    ) -> Option<GlyphMetrics> {
+        let id = ttf_parser::GlyphId(u16::try_from(glyph_id).ok()?);
+        let face = self.faces.get(font, coords)?;
+        let upem = f64::from(face.units_per_em());
+        let scale = |units: i16| f64::from(units) * f64::from(size) / upem;
+        let advance = face.glyph_hor_advance(id)?;
+        let bounds = face.glyph_bounding_box(id).map(|bbox| {
+            Rect::new(
+                scale(bbox.x_min),
+                scale(bbox.y_min),
+                scale(bbox.x_max),
+                scale(bbox.y_max),
+            )
+        });
+        Some(GlyphMetrics {
+            advance: (f64::from(advance) * f64::from(size) / upem) as f32,
+            bounds,
+        })
+    }
+}
+
+fn parse_face<'a>(font: &'a Font, coords: &[NormalizedCoord]) -> Option<ttf_parser::Face<'a>> {
+    let mut face = ttf_parser::Face::parse(font.data.data(), font.index).ok()?;
+    crate::outline::set_variations(&mut face, coords);
+    Some(face)
+}
+
+/// Glyph provider that draws every glyph as a rectangle, for testing the
+/// resolution of glyph runs without real fonts.
+///
+/// Glyph 0 has no outline. Every other glyph is a rectangle half an em wide
+/// and a full em tall, which is advanced by its width. The font and the
+/// variation coordinates are ignored, and hinting rounds the rectangle to
+/// whole pixels.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct MockGlyphProvider {
+    /// Number of outlines requested so far.
+    pub(crate) n_outlines: usize,
+}
+
+#[cfg(test)]
+impl MockGlyphProvider {
+    fn bounds(glyph_id: u32, size: f32, hint: bool) -> Option<Rect> {
+        if glyph_id == 0 {
+            return None;
+        }
+        let rect = Rect::new(0.0, 0.0, f64::from(size) * 0.5, f64::from(size));
+        Some(if hint { rect.round() } else { rect })
+    }
+}
+
+#[cfg(test)]
+impl GlyphProvider for MockGlyphProvider {
+    fn outline(
+        &mut self,
+        _font: &Font,
+        glyph_id: u32,
+        size: f32,
+        _coords: &[NormalizedCoord],
+        hint: bool,
+        path: &mut PathEncoder<'_>,
+    ) {
+        self.n_outlines += 1;
+        if let Some(rect) = Self::bounds(glyph_id, size, hint) {
+            path.shape(&rect);
+        }
+    }
+
+    fn metrics(
+        &mut self,
+        _font: &Font,
+        glyph_id: u32,
+        size: f32,
+        _coords: &[NormalizedCoord],
+    ) -> Option<GlyphMetrics> {
+        Some(GlyphMetrics {
+            advance: size * 0.5,
+            bounds: Self::bounds(glyph_id, size, false),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::kurbo::Rect;
+    use peniko::{Blob, Font};
+
+    use super::{GlyphMetrics, GlyphProvider, MockGlyphProvider, TtfGlyphProvider};
+    use crate::test_font::TestFont;
+
+    #[test]
+    fn ttf_metrics() {
+        let font = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let mut provider = TtfGlyphProvider::default();
+        let metrics = provider
+            .metrics(&font, TestFont::SQUARE, 20.0, &[])
+            .unwrap();
+        // 700 units of advance at 20 pixels per 1000 units.
+        assert_eq!(metrics.advance, 14.0);
+        assert_eq!(metrics.bounds, Some(Rect::new(2.0, 0.0, 12.0, 10.0)));
+        let space = provider.metrics(&font, TestFont::SPACE, 20.0, &[]).unwrap();
+        assert_eq!(space.bounds, None);
+        assert_eq!(provider.metrics(&font, 70_000, 20.0, &[]), None);
+        let invalid = Font::new(Blob::from(vec![0_u8; 8]), 0);
+        assert_eq!(
+            provider.metrics(&invalid, TestFont::SQUARE, 20.0, &[]),
+            None
+        );
+        // The cached face is replaced when the font changes.
+        let metrics = provider.metrics(&font, TestFont::SQUARE, 20.0, &[]);
+        assert_eq!(metrics.map(|metrics| metrics.advance), Some(14.0));
+    }
+
+    #[test]
+    fn mock_metrics() {
+        let font = Font::new(Blob::from(vec![0_u8; 8]), 0);
+        let mut provider = MockGlyphProvider::default();
+        assert_eq!(
+            provider.metrics(&font, 5, 10.0, &[]),
+            Some(GlyphMetrics {
+                advance: 5.0,
+                bounds: Some(Rect::new(0.0, 0.0, 5.0, 10.0)),
+            })
+        );
+    }
+}
@@ -18,6 +18,36 @@ pub struct Ramps<'a> {
     pub height: u32,
 }
 
+impl Ramps<'_> {
+    /// Writes the ramps into `out` as premultiplied RGBA8, one ramp per row
+    /// of `stride` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is shorter than a ramp or `out` is shorter than
+    /// `height` rows.
+    pub fn compose_into(&self, out: &mut [u8], stride: usize) {
+        let row_size = self.width as usize * 4;
+        assert!(stride >= row_size, "stride is shorter than a ramp");
+        assert!(
+            out.len() >= stride * self.height as usize,
+            "buffer is too small for the ramps"
+        );
+        if row_size == 0 {
+            return;
+        }
+        for (ramp, row) in self
+            .data
+            .chunks_exact(self.width as usize)
+            .zip(out.chunks_mut(stride))
+        {
+            for (src, dst) in ramp.iter().zip(row.chunks_exact_mut(4)) {
+                dst.copy_from_slice(&src.to_ne_bytes());
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct RampCache {
     epoch: u64,
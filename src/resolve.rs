@@ -1,16 +1,22 @@
 // Copyright 2022 the Vello Authors
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 
+use std::borrow::Cow;
 use std::ops::Range;
 use std::sync::Arc;
 
 use bytemuck::{Pod, Zeroable};
-use peniko::{Extend, Image};
+use peniko::kurbo::Stroke;
+use peniko::{Blob, Extend, Image};
 
 use super::{DrawTag, Encoding, PathTag, StreamOffsets, Style, Transform};
-use crate::glyph_cache::GlyphCache;
-use crate::image_cache::{ImageCache, Images};
+use crate::image_cache::{AtlasLocation, ImageCache, Images};
+use crate::outline::OutlineCache;
+use crate::pixel_format::{AlphaType, PixelFormat};
+use crate::provider::{GlyphProvider, TtfGlyphProvider};
 use crate::ramp_cache::{RampCache, Ramps};
+#[cfg(feature = "gpu_text")]
+use crate::{GlyphRun, glyph_cache::GlyphCache};
 
 /// Layout of a packed encoding.
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
@@ -154,41 +160,122 @@ pub fn resolve_solid_paths_only(encoding: &Encoding, packed: &mut Vec<u8>) -> La
 }
 
 /// Resolver for late bound resources.
-pub struct Resolver {
-    glyph_cache: GlyphCache,
+///
+/// Gradient ramps and images are resolved on the CPU and glyph runs are
+/// expanded into paths using outlines read from the font data, so no GPU
+/// context is needed. With the `gpu_text` feature, a resolver created with
+/// `Resolver::with_gpu` additionally prepares the text of glyph runs that
+/// carry a glyphon buffer with the blitz-text system.
+///
+/// Outlines come from a [`GlyphProvider`], which defaults to reading them
+/// from the font data with `ttf_parser`.
+#[derive(Default)]
+pub struct Resolver<P = TtfGlyphProvider> {
+    outline_cache: OutlineCache<P>,
+    #[cfg(feature = "gpu_text")]
+    glyph_cache: Option<GlyphCache>,
     glyphs: Vec<Arc<Encoding>>,
     ramp_cache: RampCache,
     image_cache: ImageCache,
     pending_images: Vec<PendingImage>,
     patches: Vec<ResolvedPatch>,
+    /// Number of horizontal subpixel positions of hinted glyphs.
+    subpixel_positions: u8,
 }
 
 impl Resolver {
     /// Creates a new resource cache.
     pub fn new() -> Self {
-        // Note: GlyphCache requires proper initialization with GPU device/queue
-        // This will need to be updated when a Resolver is created with GPU context
-        panic!("Resolver::new() requires GPU context - use Resolver::with_gpu() instead")
+        Self::default()
     }
 
     /// Creates a new resource cache with GPU context for blitz-text integration
+    #[cfg(feature = "gpu_text")]
     pub fn with_gpu(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         format: wgpu::TextureFormat,
     ) -> Result<Self, blitz_text::TextSystemError> {
         Ok(Self {
-            glyph_cache: crate::glyph_cache::GlyphCache::new(device, queue, format)?,
+            glyph_cache: Some(GlyphCache::new(device, queue, format)?),
+            ..Self::default()
+        })
+    }
+}
+
+impl<P: GlyphProvider> Resolver<P> {
+    /// Creates a new resource cache that reads glyph outlines from
+    /// `provider`.
+    pub fn with_provider(provider: P) -> Self {
+        Self {
+            outline_cache: OutlineCache::new(provider),
+            #[cfg(feature = "gpu_text")]
+            glyph_cache: None,
             glyphs: Vec::new(),
             ramp_cache: RampCache::default(),
             image_cache: ImageCache::default(),
             pending_images: Vec::new(),
             patches: Vec::new(),
-        })
+            subpixel_positions: 0,
+        }
+    }
+
+    /// Returns the glyph provider.
+    pub fn provider(&self) -> &P {
+        &self.outline_cache.provider
+    }
+
+    /// Returns the glyph provider for modification.
+    ///
+    /// Outlines that were already resolved stay cached until they are
+    /// evicted, so changes to the provider's outlines may not take effect
+    /// immediately.
+    pub fn provider_mut(&mut self) -> &mut P {
+        &mut self.outline_cache.provider
+    }
+
+    /// Sets the number of horizontal subpixel positions of hinted glyphs.
+    ///
+    /// The x-coordinate of each hinted glyph is quantized to `positions`
+    /// steps per pixel, so that outlines are shared by all glyphs in the same
+    /// step and a glyph doesn't jitter when it moves by less than a step.
+    /// Zero, the default, leaves positions untouched and one snaps them to
+    /// whole pixels. Runs with a glyph transform are never quantized.
+    pub fn with_subpixel_positions(mut self, positions: u8) -> Self {
+        self.subpixel_positions = positions;
+        self
+    }
+
+    /// Sets the number of border pixels around each image in the atlas.
+    ///
+    /// The border is filled according to the extend modes of the image, so
+    /// that filtered sampling near its edges doesn't bleed neighbouring
+    /// images. The default of two pixels covers bicubic sampling, and zero
+    /// packs images edge to edge. Images that were already placed in the
+    /// atlas are dropped.
+    pub fn with_image_padding(mut self, padding: u32) -> Self {
+        self.image_cache = ImageCache::new(padding);
+        self
+    }
+
+    /// Sets the pixel format of images with the given data.
+    ///
+    /// Images are converted to premultiplied RGBA8 when they are placed in
+    /// the atlas, reading their pixels in this format instead of the format
+    /// of the image. Images that can't be converted aren't drawn and are
+    /// reported in the [`Images`] returned by [`resolve`](Self::resolve).
+    pub fn set_image_format(&mut self, data: &Blob<u8>, format: PixelFormat, alpha: AlphaType) {
+        self.image_cache.set_format(data, format, alpha);
     }
 
     /// Resolves late bound resources and packs an encoding. Returns the packed
     /// layout and computed ramp data.
+    ///
+    /// This is async because preparing text with the GPU context of the
+    /// `gpu_text` feature waits on blitz-text. Without that feature, or with
+    /// a resolver that has no GPU context, the future is ready the first
+    /// time it is polled, so any executor, including a minimal `block_on`,
+    /// can drive it.
     pub async fn resolve<'a>(
         &'a mut self,
         encoding: &Encoding,
@@ -221,7 +308,8 @@ impl Resolver {
             for patch in &self.patches {
                 if let ResolvedPatch::GlyphRun { index, glyphs, .. } = patch {
                     layout.n_paths += 1;
-                    let stream_offset = resources.glyph_runs[*index].stream_offsets.path_tags;
+                    let run = &resources.glyph_runs[*index];
+                    let stream_offset = run.stream_offsets.path_tags;
                     if pos < stream_offset {
                         data.extend_from_slice(bytemuck::cast_slice(&stream[pos..stream_offset]));
                         pos = stream_offset;
@@ -230,6 +318,11 @@ impl Resolver {
                         data.extend_from_slice(bytemuck::bytes_of(&PathTag::TRANSFORM));
                         data.extend_from_slice(bytemuck::cast_slice(&glyph.path_tags));
                     }
+                    // The brush transform precedes the path marker, as for
+                    // any other draw object.
+                    if run.brush_transform.is_some() {
+                        data.extend_from_slice(bytemuck::bytes_of(&PathTag::TRANSFORM));
+                    }
                     data.extend_from_slice(bytemuck::bytes_of(&PathTag::PATH));
                 }
             }
@@ -305,43 +398,24 @@ impl Resolver {
                                 &encoding.draw_data[pos..*draw_data_offset],
                             ));
                         }
-                        if let Some((x, y)) = self.pending_images[*index].xy {
-                            let xy = (x << 16) | y;
-                            data.extend_from_slice(bytemuck::bytes_of(&xy));
-                            pos = *draw_data_offset + 1;
+                        if let Some(location) = self.pending_images[*index].location {
+                            let xy = (location.x << 16) | location.y;
+                            let width_height = encoding.draw_data[*draw_data_offset + 1];
+                            // The page index goes into the unused high bits
+                            // of the sample and alpha word.
+                            let sample_alpha =
+                                encoding.draw_data[*draw_data_offset + 2] | (location.page << 16);
+                            data.extend_from_slice(bytemuck::bytes_of(&[
+                                xy,
+                                width_height,
+                                sample_alpha,
+                            ]));
+                            pos = *draw_data_offset + 3;
                         } else {
-                            // Atlas allocation failed - implement bump_size strategy from image_cache.rs
-                            // Double the atlas size and retry allocation for large images
-                            if self.image_cache.bump_size() {
-                                // Retry allocation after doubling atlas size
-                                if let Some((x, y)) = self
-                                    .image_cache
-                                    .get_or_insert(&self.pending_images[*index].image)
-                                {
-                                    self.pending_images[*index].xy = Some((x, y));
-                                    let xy = (x << 16) | y;
-                                    data.extend_from_slice(bytemuck::bytes_of(&xy));
-                                    pos = *draw_data_offset + 1;
-                                } else {
-                                    // Even after bump_size, allocation failed - render at reduced size
-                                    let reduced_image =
-                                        self.downsample_image(&self.pending_images[*index].image);
-                                    if let Some((x, y)) =
-                                        self.image_cache.get_or_insert(&reduced_image)
-                                    {
-                                        self.pending_images[*index].xy = Some((x, y));
-                                        let xy = (x << 16) | y;
-                                        data.extend_from_slice(bytemuck::bytes_of(&xy));
-                                        pos = *draw_data_offset + 1;
-                                    } else {
-                                        data.extend_from_slice(&[0_u8; 8]);
-                                        pos = *draw_data_offset + 2;
-                                    }
-                                }
-                            } else {
-                                data.extend_from_slice(&[0_u8; 8]);
-                                pos = *draw_data_offset + 2;
-                            }
+                            // If we get here, the image couldn't be placed in
+                            // the atlas, so it is drawn empty.
+                            data.extend_from_slice(&[0_u8; 8]);
+                            pos = *draw_data_offset + 2;
                         }
                     }
                 }
@@ -362,6 +436,7 @@ impl Resolver {
                     transform,
                     scale,
                     hint,
+                    subpixel_positions,
                 } = patch
                 {
                     let run = &resources.glyph_runs[*index];
@@ -370,7 +445,7 @@ impl Resolver {
                         data.extend_from_slice(bytemuck::cast_slice(&stream[pos..stream_offset]));
                         pos = stream_offset;
                     }
-                    if let Some(glyph_transform) = run.glyph_transform {
+                    if let Some(glyph_transform) = run.effective_glyph_transform() {
                         for glyph in &resources.glyphs[run.glyphs.clone()] {
                             let mut xform = *transform
                                 * Transform {
@@ -393,9 +468,19 @@ impl Resolver {
                             if *hint {
                                 xform.translation[1] = xform.translation[1].round();
                             }
+                            if *subpixel_positions != 0 {
+                                let (x, _) = quantize_x(xform.translation[0], *subpixel_positions);
+                                xform.translation[0] = x;
+                            }
                             data.extend_from_slice(bytemuck::bytes_of(&xform));
                         }
                     }
+                    if let Some(brush_transform) = run.brush_transform {
+                        // Relative to the unhinted run transform, so that
+                        // the brush isn't affected by hinting.
+                        let xform = run.transform * brush_transform;
+                        data.extend_from_slice(bytemuck::bytes_of(&xform));
+                    }
                 }
             }
             if pos < stream.len() {
@@ -431,9 +516,13 @@ impl Resolver {
 
     async fn resolve_patches(&mut self, encoding: &Encoding) -> StreamOffsets {
         self.ramp_cache.maintain();
+        self.outline_cache.maintain();
         self.glyphs.clear();
-        self.glyph_cache.maintain();
-        self.image_cache.clear();
+        #[cfg(feature = "gpu_text")]
+        if let Some(glyph_cache) = &mut self.glyph_cache {
+            glyph_cache.maintain();
+        }
+        self.image_cache.maintain();
         self.pending_images.clear();
         self.patches.clear();
         let mut sizes = StreamOffsets::default();
@@ -456,7 +545,7 @@ impl Resolver {
                     let mut run_sizes = StreamOffsets::default();
                     let run = &resources.glyph_runs[*index];
                     let glyphs = &resources.glyphs[run.glyphs.clone()];
-                    let _coords = &resources.normalized_coords[run.normalized_coords.clone()];
+                    let coords = &resources.normalized_coords[run.normalized_coords.clone()];
                     let mut hint = run.hint;
                     let mut font_size = run.font_size;
                     let mut transform = run.transform;
@@ -476,40 +565,56 @@ impl Resolver {
                             hint = false;
                         }
                     }
-                    // Extract text from the run for blitz-text processing
-                    let text = if let Some(buffer) = &run.buffer {
-                        // Extract text content from buffer
-                        buffer
-                            .layout_runs()
-                            .map(|layout_run| layout_run.text)
-                            .collect::<String>()
+                    // Subpixel offsets are baked into the outlines, which
+                    // only works if nothing is applied on top of them.
+                    let subpixel_positions = if hint && run.effective_glyph_transform().is_none() {
+                        self.subpixel_positions
                     } else {
-                        // Fallback: create placeholder text
-                        "placeholder".to_string()
+                        0
                     };
-
-                    // Create session for text-based processing
-                    let mut session = match self
-                        .glyph_cache
-                        .session(&run.font, font_size, &run.style, &text)
-                        .await
-                    {
-                        Ok(session) => session,
-                        Err(_) => continue,
-                    };
-
+                    #[cfg(feature = "gpu_text")]
+                    self.prepare_gpu_text(run, font_size).await;
                     let glyph_start = self.glyphs.len();
-                    // Get text encoding for the entire text string (replaces per-glyph processing)
-                    let (encoding, stream_sizes) = session.get_text_encoding();
-                    run_sizes.add(&stream_sizes);
-
-                    // Add one encoding per glyph to maintain compatibility with existing structure
-                    for _glyph in glyphs {
-                        self.glyphs.push(encoding.clone());
+                    // Strokes keep their width relative to the glyphs when
+                    // hinting moves the scale into the outlines.
+                    let style = match &run.style {
+                        peniko::Style::Stroke(stroke) if scale != 1.0 => {
+                            Cow::Owned(peniko::Style::Stroke(Stroke {
+                                width: stroke.width * f64::from(scale.abs()),
+                                ..stroke.clone()
+                            }))
+                        }
+                        style => Cow::Borrowed(style),
+                    };
+                    let mut session = self.outline_cache.session(
+                        &run.font,
+                        coords,
+                        font_size,
+                        hint,
+                        run.embolden * scale.abs(),
+                        &style,
+                    );
+                    for glyph in glyphs {
+                        let (encoding, stream_sizes) = if subpixel_positions == 0 {
+                            session.get(glyph.id)
+                        } else {
+                            // Matches the glyph transform computed when
+                            // packing the transform stream.
+                            let xform = transform
+                                * Transform {
+                                    matrix: [1.0, 0.0, 0.0, -1.0],
+                                    translation: [glyph.x * scale, glyph.y * scale],
+                                };
+                            let (_, offset) = quantize_x(xform.translation[0], subpixel_positions);
+                            session.get_offset(glyph.id, offset)
+                        };
+                        run_sizes.add(&stream_sizes);
+                        self.glyphs.push(encoding);
                     }
                     let glyph_end = self.glyphs.len();
-                    run_sizes.path_tags += glyphs.len() + 1;
-                    run_sizes.transforms += glyphs.len();
+                    let n_brush_transforms = usize::from(run.brush_transform.is_some());
+                    run_sizes.path_tags += glyphs.len() + 1 + n_brush_transforms;
+                    run_sizes.transforms += glyphs.len() + n_brush_transforms;
                     sizes.add(&run_sizes);
                     self.patches.push(ResolvedPatch::GlyphRun {
                         index: *index,
@@ -517,6 +622,7 @@ impl Resolver {
                         transform,
                         scale,
                         hint,
+                        subpixel_positions,
                     });
                 }
                 Patch::Image {
@@ -530,7 +636,7 @@ impl Resolver {
                     resolved_image.alpha *= alpha_multiplier;
                     self.pending_images.push(PendingImage {
                         image: resolved_image,
-                        xy: None,
+                        location: None,
                     });
                     self.patches.push(ResolvedPatch::Image {
                         index,
@@ -542,66 +648,33 @@ impl Resolver {
         sizes
     }
 
-    fn resolve_pending_images(&mut self) {
-        self.image_cache.clear();
-        'outer: loop {
-            // Loop over the images, attempting to allocate them all into the atlas.
-            for pending_image in &mut self.pending_images {
-                if let Some(xy) = self.image_cache.get_or_insert(&pending_image.image) {
-                    pending_image.xy = Some(xy);
-                } else {
-                    // We failed to allocate. Try to bump the atlas size.
-                    if self.image_cache.bump_size() {
-                        // We were able to increase the atlas size. Restart the outer loop.
-                        continue 'outer;
-                    } else {
-                        // If the atlas is already maximum size, there's nothing we can do. Set
-                        // the xy field to None so this image isn't rendered and then carry on--
-                        // other images might still fit.
-                        pending_image.xy = None;
-                    }
-                }
-            }
-            // If we made it here, we've either successfully allocated all images or we reached
-            // the maximum atlas size.
-            break;
-        }
+    /// Prepares the text of a run that carries a glyphon buffer with the
+    /// blitz-text system, if the resolver has a GPU context.
+    ///
+    /// The glyphs themselves are always encoded from the font outlines.
+    #[cfg(feature = "gpu_text")]
+    async fn prepare_gpu_text(&mut self, run: &GlyphRun, font_size: f32) {
+        let (Some(glyph_cache), Some(buffer)) = (&mut self.glyph_cache, &run.buffer) else {
+            return;
+        };
+        let text = buffer
+            .layout_runs()
+            .map(|layout_run| layout_run.text)
+            .collect::<String>();
+        // Failing to prepare the text doesn't affect the encoded outlines.
+        let _ = glyph_cache
+            .session(&run.font, font_size, &run.style, &text)
+            .await;
     }
 
-    /// Downsample large images that exceed atlas capacity using bilinear filtering
-    fn downsample_image(&self, image: &peniko::Image) -> peniko::Image {
-        use peniko::Image;
-
-        // Calculate target dimensions (halve both width and height)
-        let target_width = (image.width / 2).max(1);
-        let target_height = (image.height / 2).max(1);
-
-        // Simple pixel sampling downsampling - take every other pixel
-        let mut downsampled_data = Vec::with_capacity((target_width * target_height * 4) as usize);
-        let source_data = image.data.as_ref();
-
-        for y in 0..target_height {
-            for x in 0..target_width {
-                let src_x = (x * 2).min(image.width - 1);
-                let src_y = (y * 2).min(image.height - 1);
-                let src_idx = ((src_y * image.width + src_x) * 4) as usize;
-
-                // Copy 4 bytes (RGBA) from source to downsampled
-                if src_idx + 3 < source_data.len() {
-                    downsampled_data.extend_from_slice(&source_data[src_idx..src_idx + 4]);
-                } else {
-                    downsampled_data.extend_from_slice(&[0, 0, 0, 255]); // Default to transparent black
-                }
-            }
+    fn resolve_pending_images(&mut self) {
+        for pending_image in &mut self.pending_images {
+            // The cache evicts unused images, grows the atlas and adds pages
+            // as needed, so failing here means the image can't fit at all.
+            // Leave the location as None so it isn't rendered and carry on,
+            // other images might still fit.
+            pending_image.location = self.image_cache.get_or_insert(&pending_image.image);
         }
-
-        // Create new image with downsampled data
-        Image::new(
-            peniko::Blob::new(std::sync::Arc::new(downsampled_data)),
-            peniko::ImageFormat::Rgba8,
-            target_width,
-            target_height,
-        )
     }
 }
 
@@ -633,11 +706,20 @@ pub enum Patch {
     },
 }
 
+/// Splits a horizontal glyph position into whole pixels and a fractional
+/// offset quantized to `positions` steps per pixel.
+fn quantize_x(x: f32, positions: u8) -> (f32, f32) {
+    let steps = f32::from(positions);
+    let x = (x * steps).round() / steps;
+    let whole = x.floor();
+    (whole, x - whole)
+}
+
 /// Image to be allocated in the atlas.
 #[derive(Clone, Debug)]
 struct PendingImage {
     image: Image,
-    xy: Option<(u32, u32)>,
+    location: Option<AtlasLocation>,
 }
 
 #[derive(Clone, Debug)]
@@ -664,6 +746,9 @@ enum ResolvedPatch {
         /// This determines whether the y-coordinate of the final position
         /// needs to be rounded.
         hint: bool,
+        /// Number of steps per pixel the x-coordinate of the final position
+        /// is quantized to, or zero if it is used as is.
+        subpixel_positions: u8,
     },
     Image {
         /// Index of pending image element.
@@ -714,3 +799,316 @@ fn size_to_words(byte_size: usize) -> u32 {
 fn align_up(len: usize, alignment: u32) -> usize {
     len + (len.wrapping_neg() & (alignment as usize - 1))
 }
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    use peniko::kurbo::{Affine, Join, Rect, Stroke};
+    use peniko::{Blob, Color, ColorStop, Fill, Font, Gradient, Image, ImageFormat};
+
+    use super::Resolver;
+    use crate::image_cache::ImageCache;
+    use crate::provider::MockGlyphProvider;
+    use crate::test_font::TestFont;
+    use crate::{AlphaType, Glyph, ImageError, PixelFormat, SceneBuilder, Style};
+
+    /// Polls a future that never waits to completion.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_without_gpu() {
+        let mut builder = SceneBuilder::new();
+        let gradient = Gradient::new_linear((0.0, 0.0), (1.0, 0.0)).with_stops(
+            [
+                ColorStop::from((0.0, Color::BLACK)),
+                ColorStop::from((1.0, Color::WHITE)),
+            ]
+            .as_slice(),
+        );
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        builder.fill(Fill::NonZero, Affine::IDENTITY, &gradient, None, &rect);
+        let image = Image::new(Blob::from(vec![0_u8; 16]), ImageFormat::Rgba8, 2, 2);
+        builder.draw_image(&image, Affine::IDENTITY);
+        let font = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let glyphs = [TestFont::SQUARE, TestFont::TRIANGLE, TestFont::SPACE]
+            .into_iter()
+            .enumerate()
+            .map(|(ix, id)| Glyph {
+                id,
+                x: ix as f32 * 10.0,
+                y: 20.0,
+            });
+        builder
+            .draw_glyphs(&font)
+            .font_size(10.0)
+            .draw(Fill::NonZero, glyphs);
+        let encoding = builder.finish();
+
+        let mut resolver = Resolver::new();
+        let mut packed = Vec::new();
+        let (layout, ramps, images) = block_on(resolver.resolve(&encoding, &mut packed));
+        assert_eq!(layout.n_paths, encoding.n_paths + 1);
+        assert_eq!(layout.n_draw_objects, 3);
+        assert_eq!(ramps.height, 1);
+        assert_eq!(images.pages[0].images.len(), 1);
+        assert_eq!(
+            layout.transforms(&packed).len(),
+            encoding.transforms.len() + 3
+        );
+        let n_segments =
+            |tags: &[crate::PathTag]| tags.iter().filter(|tag| tag.is_path_segment()).count();
+        // Four lines for the square, a line and a quad for the triangle.
+        assert_eq!(
+            n_segments(layout.path_tags(&packed)),
+            n_segments(&encoding.path_tags) + 6
+        );
+        // The glyph is flipped and placed at its offset.
+        let glyph_transform = layout.transforms(&packed)[encoding.transforms.len() + 1];
+        assert_eq!(glyph_transform.matrix, [1.0, 0.0, 0.0, -1.0]);
+        assert_eq!(glyph_transform.translation, [10.0, 20.0]);
+        // The image stays in the atlas, so it is only uploaded once.
+        let (_, _, images) = block_on(resolver.resolve(&encoding, &mut packed));
+        assert_eq!(images.pages[0].images.len(), 1);
+        assert!(images.pages[0].uploads.is_empty());
+    }
+
+    #[test]
+    fn compose_resources() {
+        let mut builder = SceneBuilder::new();
+        let gradient = Gradient::new_linear((0.0, 0.0), (1.0, 0.0)).with_stops(
+            [
+                ColorStop::from((0.0, Color::BLACK)),
+                ColorStop::from((1.0, Color::WHITE)),
+            ]
+            .as_slice(),
+        );
+        let rect = Rect::new(0.0, 0.0, 4.0, 4.0);
+        builder.fill(Fill::NonZero, Affine::IDENTITY, &gradient, None, &rect);
+        let image = Image::new(Blob::from(vec![255_u8; 4]), ImageFormat::Rgba8, 1, 1);
+        builder.draw_image(&image, Affine::IDENTITY);
+        let encoding = builder.finish();
+
+        let mut resolver = Resolver::new();
+        let mut packed = Vec::new();
+        let (_, ramps, images) = block_on(resolver.resolve(&encoding, &mut packed));
+        let stride = ramps.width as usize * 4;
+        let mut ramp_data = vec![0_u8; stride * ramps.height as usize];
+        ramps.compose_into(&mut ramp_data, stride);
+        assert_eq!(ramp_data[..4], [0, 0, 0, 255]);
+        assert_eq!(ramp_data[stride - 4..], [255, 255, 255, 255]);
+        // The image is white with a white border of the default padding.
+        let stride = images.width as usize * 4;
+        let mut atlas = vec![0_u8; images.buffer_size(stride)];
+        images.compose_into(&mut atlas, stride);
+        let white = atlas
+            .chunks(stride)
+            .take_while(|row| row[0] == 255)
+            .map(|row| row.iter().take_while(|&&c| c == 255).count() / 4)
+            .collect::<Vec<_>>();
+        assert_eq!(white, [5; 5]);
+    }
+
+    #[test]
+    fn image_formats() {
+        let gray = Blob::from(vec![0_u8, 64, 128, 255]);
+        let images = [
+            Image::new(gray.clone(), ImageFormat::Rgba8, 2, 2),
+            Image::new(Blob::from(vec![0_u8; 4]), ImageFormat::Rgba8, 2, 2),
+        ];
+        let mut builder = SceneBuilder::new();
+        for image in &images {
+            builder.draw_image(image, Affine::IDENTITY);
+        }
+        let encoding = builder.finish();
+
+        let mut resolver = Resolver::new().with_image_padding(0);
+        resolver.set_image_format(&gray, PixelFormat::Gray8, AlphaType::Straight);
+        let mut packed = Vec::new();
+        let (_, _, images) = block_on(resolver.resolve(&encoding, &mut packed));
+        // The gray image is expanded to RGBA, while the truncated RGBA image
+        // is reported instead of being read past its data.
+        let page = &images.pages[0];
+        assert_eq!(page.images.len(), 1);
+        assert_eq!(page.images[0].image.data.data()[4..8], [64, 64, 64, 255]);
+        assert!(matches!(
+            images.errors,
+            [(
+                _,
+                ImageError::Truncated {
+                    expected: 16,
+                    actual: 4
+                }
+            )]
+        ));
+    }
+
+    #[test]
+    fn image_pages() {
+        // Two images that each fill an atlas page at its maximum size.
+        let [a, b] =
+            [(); 2].map(|_| Image::new(Blob::from(vec![0_u8; 1024]), ImageFormat::Rgba8, 16, 16));
+        let mut builder = SceneBuilder::new();
+        builder.draw_image(&a, Affine::IDENTITY);
+        builder.draw_image(&b, Affine::IDENTITY);
+        let encoding = builder.finish();
+
+        let mut resolver = Resolver::new();
+        // Pages of the real maximum size would need huge images.
+        resolver.image_cache = ImageCache::with_max_size(0, 16);
+        let mut packed = Vec::new();
+        let (layout, _, images) = block_on(resolver.resolve(&encoding, &mut packed));
+        assert_eq!(images.pages.len(), 2);
+        assert!(images.pages.iter().all(|page| page.uploads.len() == 1));
+        // Both images are drawn, the second one from the second page.
+        let draw_data = layout.draw_data(&packed);
+        assert_eq!(draw_data.len(), 6);
+        assert_eq!(draw_data[0], 0);
+        assert_eq!(draw_data[2] >> 16, 0);
+        assert_eq!(draw_data[3], 0);
+        assert_eq!(draw_data[4], (16 << 16) | 16);
+        assert_eq!(draw_data[5] >> 16, 1);
+        assert_eq!(draw_data[5] & 0xFFFF, encoding.draw_data[5]);
+    }
+
+    #[test]
+    fn custom_provider() {
+        let font = Font::new(Blob::from(vec![0_u8; 4]), 0);
+        let mut builder = SceneBuilder::new();
+        let glyphs = [1, 0, 1].map(|id| Glyph {
+            id,
+            x: id as f32 * 10.0,
+            y: 0.0,
+        });
+        builder
+            .draw_glyphs(&font)
+            .font_size(10.0)
+            .draw(Fill::NonZero, glyphs.into_iter());
+        let encoding = builder.finish();
+        let mut resolver = Resolver::with_provider(MockGlyphProvider::default());
+        let mut packed = Vec::new();
+        for _ in 0..2 {
+            let (layout, ..) = block_on(resolver.resolve(&encoding, &mut packed));
+            // Two rectangles, glyph 0 has no outline.
+            let n_segments = layout
+                .path_tags(&packed)
+                .iter()
+                .filter(|tag| tag.is_path_segment())
+                .count();
+            assert_eq!(n_segments, 8);
+        }
+        // Each distinct glyph is requested once.
+        assert_eq!(resolver.provider().n_outlines, 2);
+    }
+
+    #[test]
+    fn subpixel_positions() {
+        let font = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let mut builder = SceneBuilder::new();
+        let glyphs = [0.3, 5.3, 6.45].map(|x| Glyph {
+            id: TestFont::SQUARE,
+            x,
+            y: 10.2,
+        });
+        builder
+            .draw_glyphs(&font)
+            .font_size(10.0)
+            .transform(Affine::scale(2.0))
+            .hint(true)
+            .draw(Fill::NonZero, glyphs.into_iter());
+        let encoding = builder.finish();
+        let mut resolver = Resolver::new().with_subpixel_positions(4);
+        let mut packed = Vec::new();
+        let (layout, ..) = block_on(resolver.resolve(&encoding, &mut packed));
+        // Positions are split into whole pixels and quarter pixel offsets,
+        // which are part of the outlines.
+        let transforms = &layout.transforms(&packed)[encoding.transforms.len()..];
+        let translations = transforms.iter().map(|xform| xform.translation);
+        assert!(translations.eq([[0.0, 20.0], [10.0, 20.0], [13.0, 20.0]]));
+        let data = layout.path_data(&packed);
+        let n = data.len() / 3;
+        assert_eq!(data[..n], data[n..2 * n]);
+        assert_ne!(data[n..2 * n], data[2 * n..]);
+    }
+
+    #[test]
+    fn stroked_text() {
+        let font = Font::new(Blob::from(TestFont::new(1000).build()), 0);
+        let mut resolver = Resolver::new();
+        let mut resolve = |hint: bool| {
+            let mut builder = SceneBuilder::new();
+            let glyph = Glyph {
+                id: TestFont::SQUARE,
+                x: 0.0,
+                y: 0.0,
+            };
+            let stroke = Stroke::new(2.0).with_join(Join::Round);
+            builder
+                .draw_glyphs(&font)
+                .font_size(10.0)
+                .transform(Affine::scale(2.0))
+                .hint(hint)
+                .draw(&stroke, [glyph].into_iter());
+            let encoding = builder.finish();
+            let mut packed = Vec::new();
+            let (layout, ..) = block_on(resolver.resolve(&encoding, &mut packed));
+            let n_segments = layout
+                .path_tags(&packed)
+                .iter()
+                .filter(|tag| tag.is_path_segment())
+                .count();
+            (*layout.styles(&packed).last().unwrap(), n_segments)
+        };
+        let (style, n_segments) = resolve(false);
+        assert!(!style.is_fill());
+        assert_eq!(style.line_width, 2.0);
+        assert_eq!(
+            style.flags_and_miter_limit & Style::FLAGS_JOIN_MASK,
+            Style::FLAGS_JOIN_BITS_ROUND
+        );
+        // The closed contour of the square is stroked rather than filled,
+        // which adds the cap marker segment of the stroker.
+        assert_eq!(n_segments, 5);
+        // Hinted outlines are scaled to device pixels along with the stroke.
+        let (style, _) = resolve(true);
+        assert_eq!(style.line_width, 4.0);
+    }
+
+    #[test]
+    fn variable_font_weights() {
+        let font = TestFont::new(1000).with_weight_axis().build();
+        let font = Font::new(Blob::from(font), 0);
+        let mut resolver = Resolver::new();
+        let mut resolve = |coords: &[i16]| {
+            let mut builder = SceneBuilder::new();
+            let glyph = Glyph {
+                id: TestFont::SQUARE,
+                x: 0.0,
+                y: 0.0,
+            };
+            builder
+                .draw_glyphs(&font)
+                .normalized_coords(coords)
+                .draw(Fill::NonZero, [glyph].into_iter());
+            let encoding = builder.finish();
+            let mut packed = Vec::new();
+            let (layout, ..) = block_on(resolver.resolve(&encoding, &mut packed));
+            layout.path_data(&packed).to_vec()
+        };
+        let regular = resolve(&[]);
+        let bold = resolve(&[0x4000]);
+        assert_ne!(regular, bold);
+        assert_eq!(bold, resolve(&[0x4000]));
+        assert_eq!(regular, resolve(&[0]));
+    }
+}
@@ -0,0 +1,455 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! High-level scene construction.
+
+use std::borrow::BorrowMut;
+
+use peniko::color::{AlphaColor, Srgb};
+use peniko::kurbo::{Affine, Rect, Shape, Stroke};
+use peniko::{BlendMode, BrushRef, Fill, Font, Image, StyleRef};
+
+use super::{Encoding, Glyph, GlyphRunBuilder, NormalizedCoord, Transform};
+
+/// Tolerance used when flattening shapes into dashes.
+const DASH_TOLERANCE: f64 = 0.1;
+
+/// Builder that encodes a scene from high-level drawing commands.
+///
+/// Each command encodes its transform, style, path and brush in the order
+/// the pipeline expects. Transforms and styles that match the current state
+/// aren't encoded again, draws with empty shapes or zero width strokes are
+/// skipped entirely and layers are always balanced, so the resulting
+/// [`Encoding`] satisfies the invariants checked by [`Encoding::validate`].
+///
+/// The drawing commands are also available inside a
+/// [`LayerGuard`](crate::LayerGuard), which borrows the encoding instead of
+/// owning it.
+#[derive(Clone, Default)]
+pub struct SceneBuilder<E = Encoding> {
+    pub(crate) encoding: E,
+}
+
+impl SceneBuilder {
+    /// Creates a new builder with an empty encoding.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all content while keeping the allocations.
+    pub fn reset(&mut self) {
+        self.encoding.reset();
+    }
+
+    /// Returns the encoding built so far.
+    ///
+    /// Layers that are still open aren't closed in the returned encoding; the
+    /// resolver closes them when packing.
+    pub fn encoding(&self) -> &Encoding {
+        &self.encoding
+    }
+
+    /// Pops all open layers and returns the finished encoding.
+    pub fn finish(mut self) -> Encoding {
+        while self.encoding.n_open_clips > 0 {
+            self.pop_layer();
+        }
+        self.encoding
+    }
+
+    /// Pushes a new layer clipped by `clip` in the coordinate space given by
+    /// `transform`. All drawing until the matching [`pop_layer`](Self::pop_layer)
+    /// is blended with `blend` and `alpha`, which is clamped to `0.0..=1.0`.
+    ///
+    /// See also [`Encoding::push_layer`] for a scoped variant.
+    pub fn push_layer(
+        &mut self,
+        blend: impl Into<BlendMode>,
+        alpha: f32,
+        transform: Affine,
+        clip: &impl Shape,
+    ) {
+        self.encoding
+            .encode_layer(blend.into(), alpha, clip, Transform::from_kurbo(&transform));
+    }
+
+    /// Pops the most recently pushed layer. Does nothing if no layer is open.
+    pub fn pop_layer(&mut self) {
+        self.encoding.encode_end_clip();
+    }
+}
+
+impl<E: BorrowMut<Encoding>> SceneBuilder<E> {
+    /// Fills a shape using the specified style and brush.
+    ///
+    /// `brush_transform` maps brush space (the coordinates of gradients and
+    /// images) to the local space of the shape.
+    #[expect(
+        single_use_lifetimes,
+        reason = "False positive: https://github.com/rust-lang/rust/issues/129255"
+    )]
+    pub fn fill<'b>(
+        &mut self,
+        style: Fill,
+        transform: Affine,
+        brush: impl Into<BrushRef<'b>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let encoding = self.encoding.borrow_mut();
+        encoding.encode_transform(Transform::from_kurbo(&transform));
+        encoding.encode_fill_style(style);
+        if encoding.encode_shape(shape, true) {
+            self.encode_brush(brush, brush_transform);
+        }
+    }
+
+    /// Strokes a shape using the specified style and brush.
+    ///
+    /// Dashes are applied on the CPU before encoding. See [`fill`](Self::fill)
+    /// for the meaning of `brush_transform`.
+    #[expect(
+        single_use_lifetimes,
+        reason = "False positive: https://github.com/rust-lang/rust/issues/129255"
+    )]
+    pub fn stroke<'b>(
+        &mut self,
+        style: &Stroke,
+        transform: Affine,
+        brush: impl Into<BrushRef<'b>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let encoding = self.encoding.borrow_mut();
+        encoding.encode_transform(Transform::from_kurbo(&transform));
+        if !encoding.encode_stroke_style(style) {
+            return;
+        }
+        let encoded = if style.dash_pattern.is_empty() {
+            encoding.encode_shape(shape, false)
+        } else {
+            let dashed = peniko::kurbo::dash(
+                shape.path_elements(DASH_TOLERANCE),
+                style.dash_offset,
+                &style.dash_pattern,
+            );
+            encoding.encode_path_elements(dashed, false)
+        };
+        if encoded {
+            self.encode_brush(brush, brush_transform);
+        }
+    }
+
+    /// Draws an image at its natural size with the given transform.
+    pub fn draw_image(&mut self, image: &Image, transform: Affine) {
+        self.fill(
+            Fill::NonZero,
+            transform,
+            image,
+            None,
+            &Rect::new(0.0, 0.0, image.width as f64, image.height as f64),
+        );
+    }
+
+    /// Draws a rounded rectangle blurred with a gaussian filter.
+    ///
+    /// The drawn area is the rectangle expanded by 2.5 standard deviations,
+    /// which covers the visible extent of the blur.
+    pub fn draw_blurred_rounded_rect(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        color: AlphaColor<Srgb>,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        let kernel_size = 2.5 * std_dev;
+        let shape = rect.inflate(kernel_size, kernel_size);
+        self.draw_blurred_rounded_rect_in(&shape, transform, rect, color, radius, std_dev);
+    }
+
+    /// Draws a blurred rounded rectangle, limited to the area of `shape`.
+    ///
+    /// Both `shape` and `rect` are in the coordinate space given by
+    /// `transform`.
+    pub fn draw_blurred_rounded_rect_in(
+        &mut self,
+        shape: &impl Shape,
+        transform: Affine,
+        rect: Rect,
+        color: AlphaColor<Srgb>,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        let encoding = self.encoding.borrow_mut();
+        encoding.encode_transform(Transform::from_kurbo(&transform));
+        encoding.encode_fill_style(Fill::NonZero);
+        if encoding.encode_shape(shape, true) {
+            // The blur is evaluated relative to the center of the rectangle.
+            let brush_transform = Affine::translate(rect.center().to_vec2());
+            encoding.encode_brush_transform(Transform::from_kurbo(&brush_transform));
+            encoding.encode_blurred_rounded_rect(
+                color,
+                rect.width() as f32,
+                rect.height() as f32,
+                radius as f32,
+                std_dev as f32,
+            );
+        }
+    }
+
+    /// Returns a builder for drawing a run of glyphs from `font`.
+    pub fn draw_glyphs(&mut self, font: &Font) -> DrawGlyphs<'_> {
+        DrawGlyphs::new(self.encoding.borrow_mut(), font)
+    }
+
+    fn encode_brush<'b>(
+        &mut self,
+        brush: impl Into<BrushRef<'b>>,
+        brush_transform: Option<Affine>,
+    ) {
+        let encoding = self.encoding.borrow_mut();
+        match brush_transform {
+            Some(brush_transform) => encoding.encode_brush_with_transform(
+                brush,
+                1.0,
+                Transform::from_kurbo(&brush_transform),
+            ),
+            None => encoding.encode_brush(brush, 1.0),
+        }
+    }
+}
+
+/// Builder for a run of glyphs, returned by [`SceneBuilder::draw_glyphs`].
+#[must_use = "the glyph run is only encoded by `draw`"]
+pub struct DrawGlyphs<'a> {
+    builder: GlyphRunBuilder<'a>,
+}
+
+impl<'a> DrawGlyphs<'a> {
+    fn new(encoding: &'a mut Encoding, font: &Font) -> Self {
+        Self {
+            builder: GlyphRunBuilder::new(encoding, font),
+        }
+    }
+
+    /// Sets the global transform. This is applied to all glyphs after the
+    /// offset translation.
+    ///
+    /// The default value is the identity matrix.
+    pub fn transform(mut self, transform: Affine) -> Self {
+        self.builder.run.transform = Transform::from_kurbo(&transform);
+        self
+    }
+
+    /// Sets the per-glyph transform. This is applied to all glyphs prior to
+    /// the offset translation. This is common used for applying a shear to
+    /// simulate an oblique font.
+    ///
+    /// The default value is `None`.
+    pub fn glyph_transform(mut self, transform: Option<Affine>) -> Self {
+        self.builder.run.glyph_transform = transform.map(|xform| Transform::from_kurbo(&xform));
+        self
+    }
+
+    /// Sets the brush transform, relative to the global transform.
+    ///
+    /// The default value is `None`, which leaves the brush in the coordinate
+    /// space of the last glyph.
+    pub fn brush_transform(mut self, transform: Option<Affine>) -> Self {
+        self.builder.run.brush_transform = transform.map(|xform| Transform::from_kurbo(&xform));
+        self
+    }
+
+    /// Sets the font size in pixels per em units.
+    ///
+    /// The default value is 16.0.
+    pub fn font_size(mut self, size: f32) -> Self {
+        self.builder.run.font_size = size;
+        self
+    }
+
+    /// Sets whether to enable hinting.
+    ///
+    /// The default value is `false`.
+    pub fn hint(mut self, hint: bool) -> Self {
+        self.builder.run.hint = hint;
+        self
+    }
+
+    /// Sets the distance in pixels by which outlines are expanded to
+    /// synthesize a bold face.
+    ///
+    /// The default value is 0.0.
+    pub fn embolden(mut self, embolden: f32) -> Self {
+        self.builder.run.embolden = embolden;
+        self
+    }
+
+    /// Sets the angle in radians by which glyphs are slanted to the right to
+    /// synthesize an oblique face. The shear is applied before the per-glyph
+    /// transform.
+    ///
+    /// The default value is 0.0.
+    pub fn skew(mut self, skew: f32) -> Self {
+        self.builder.run.skew = skew;
+        self
+    }
+
+    /// Sets the normalized design space coordinates for a variable font
+    /// instance.
+    pub fn normalized_coords(mut self, coords: &[NormalizedCoord]) -> Self {
+        self.builder.set_normalized_coords(coords);
+        self
+    }
+
+    /// Sets the brush.
+    ///
+    /// The default value is solid black.
+    pub fn brush(mut self, brush: impl Into<BrushRef<'a>>) -> Self {
+        self.builder.brush = brush.into();
+        self
+    }
+
+    /// Sets an additional alpha multiplier for the brush.
+    ///
+    /// The default value is 1.0.
+    pub fn brush_alpha(mut self, alpha: f32) -> Self {
+        self.builder.brush_alpha = alpha;
+        self
+    }
+
+    /// Encodes a fill or stroke for the given sequence of glyphs and consumes
+    /// the builder.
+    ///
+    /// An empty sequence of glyphs encodes nothing.
+    pub fn draw(mut self, style: impl Into<StyleRef<'a>>, glyphs: impl Iterator<Item = Glyph>) {
+        self.builder.run.style = style.into().to_owned();
+        self.builder.glyphs(glyphs);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::kurbo::{Affine, Circle, Rect, Stroke};
+    use peniko::{Blob, Color, ColorStop, Fill, Font, Gradient, Image, ImageFormat, Mix};
+
+    use super::SceneBuilder;
+    use crate::{DrawTag, Glyph, Transform};
+
+    #[test]
+    fn draw_commands() {
+        let mut builder = SceneBuilder::new();
+        let transform = Affine::translate((10.0, 0.0));
+        builder.fill(
+            Fill::EvenOdd,
+            transform,
+            Color::WHITE,
+            None,
+            &Circle::new((0.0, 0.0), 4.0),
+        );
+        // Empty shapes and zero width strokes are skipped.
+        builder.fill(Fill::NonZero, transform, Color::WHITE, None, &Rect::ZERO);
+        let zero_width = Stroke::new(0.0);
+        builder.stroke(
+            &zero_width,
+            transform,
+            Color::WHITE,
+            None,
+            &Rect::new(0.0, 0.0, 1.0, 1.0),
+        );
+        builder.push_layer(
+            Mix::Multiply,
+            0.5,
+            Affine::IDENTITY,
+            &Rect::new(0.0, 0.0, 8.0, 8.0),
+        );
+        let gradient = Gradient::new_linear((0.0, 0.0), (1.0, 0.0)).with_stops(
+            [
+                ColorStop::from((0.0, Color::BLACK)),
+                ColorStop::from((1.0, Color::WHITE)),
+            ]
+            .as_slice(),
+        );
+        let dashed = Stroke::new(1.0).with_dashes(0.0, [2.0, 1.0]);
+        builder.stroke(
+            &dashed,
+            transform,
+            &gradient,
+            Some(Affine::scale(8.0)),
+            &Rect::new(0.0, 0.0, 6.0, 6.0),
+        );
+        let image = Image::new(Blob::from(vec![0_u8; 16]), ImageFormat::Rgba8, 2, 2);
+        builder.draw_image(&image, Affine::IDENTITY);
+        builder.draw_blurred_rounded_rect(
+            Affine::IDENTITY,
+            Rect::new(0.0, 0.0, 4.0, 2.0),
+            Color::BLACK,
+            1.0,
+            0.5,
+        );
+        let font = Font::new(Blob::from(vec![0_u8; 4]), 0);
+        builder
+            .draw_glyphs(&font)
+            .font_size(12.0)
+            .normalized_coords(&[1, 2])
+            .draw(
+                Fill::NonZero,
+                [Glyph {
+                    id: 1,
+                    x: 0.0,
+                    y: 0.0,
+                }]
+                .into_iter(),
+            );
+        // An empty run leaves no trace.
+        builder
+            .draw_glyphs(&font)
+            .normalized_coords(&[3])
+            .draw(Fill::NonZero, core::iter::empty());
+
+        let encoding = builder.finish();
+        assert!(encoding.validate().is_ok());
+        assert!(
+            encoding.draw_tags
+                == [
+                    DrawTag::COLOR,
+                    DrawTag::BEGIN_CLIP,
+                    DrawTag::LINEAR_GRADIENT,
+                    DrawTag::IMAGE,
+                    DrawTag::BLUR_RECT,
+                    DrawTag::COLOR,
+                    DrawTag::END_CLIP,
+                ]
+        );
+        assert_eq!(encoding.resources.normalized_coords, [1, 2]);
+        assert_eq!(encoding.resources.glyph_runs.len(), 1);
+        // The brush transforms of the gradient and the blurred rectangle.
+        assert!(
+            encoding
+                .transforms
+                .contains(&Transform::from_kurbo(&(transform * Affine::scale(8.0))))
+        );
+        assert!(
+            encoding
+                .transforms
+                .contains(&Transform::from_kurbo(&Affine::translate((2.0, 1.0))))
+        );
+        let paths: Vec<_> = encoding.decode_paths().collect();
+        // Fill, clip, dashes, image, blurred rectangle and end clip.
+        assert_eq!(paths.len(), 6);
+        assert!(paths[2].0.elements().len() > 8);
+    }
+
+    #[test]
+    fn unbalanced_pop() {
+        let mut builder = SceneBuilder::new();
+        builder.pop_layer();
+        builder.push_layer(Mix::Normal, 1.0, Affine::IDENTITY, &Rect::ZERO);
+        builder.pop_layer();
+        builder.pop_layer();
+        let encoding = builder.finish();
+        assert!(encoding.validate().is_ok());
+        assert_eq!(encoding.n_clips, 2);
+    }
+}
@@ -0,0 +1,929 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Versioned binary container format for [`Encoding`].
+//!
+//! The container starts with a header holding an 8 byte magic number, the
+//! format version and the number of sections that follow. Each section is
+//! identified by a four byte tag and prefixed with its length in bytes, so
+//! readers can skip sections they don't understand. All values are stored in
+//! little endian byte order.
+//!
+//! Image and font data is stored once per unique [`Blob`] in the `BLOB`
+//! section and referenced by index from patches and glyph runs.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::sync::Arc;
+
+use peniko::color::{ColorSpaceTag, DynamicColor, Flags, Missing};
+use peniko::kurbo::{Cap, Join, Stroke};
+use peniko::{Blob, ColorStop, Extend, Fill, Font, Image, ImageFormat, ImageQuality};
+
+use super::{
+    DrawTag, Encoding, Glyph, GlyphRun, NormalizedCoord, Patch, PathTag, StreamOffsets, Style,
+    Transform,
+};
+
+/// Magic number at the start of every serialized encoding.
+const MAGIC: [u8; 8] = *b"VELLOENC";
+
+/// Current version of the container format.
+///
+/// Readers reject data with a newer version. Bump this whenever the layout of
+/// an existing section changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+const SECTION_COUNTS: [u8; 4] = *b"CNTS";
+const SECTION_PATH_TAGS: [u8; 4] = *b"PTAG";
+const SECTION_PATH_DATA: [u8; 4] = *b"PDAT";
+const SECTION_DRAW_TAGS: [u8; 4] = *b"DTAG";
+const SECTION_DRAW_DATA: [u8; 4] = *b"DDAT";
+const SECTION_TRANSFORMS: [u8; 4] = *b"XFRM";
+const SECTION_STYLES: [u8; 4] = *b"STYL";
+const SECTION_BLOBS: [u8; 4] = *b"BLOB";
+const SECTION_COLOR_STOPS: [u8; 4] = *b"STOP";
+const SECTION_PATCHES: [u8; 4] = *b"PTCH";
+const SECTION_GLYPHS: [u8; 4] = *b"GLYF";
+const SECTION_GLYPH_RUNS: [u8; 4] = *b"GRUN";
+const SECTION_NORMALIZED_COORDS: [u8; 4] = *b"COOR";
+
+const PATCH_RAMP: u8 = 0;
+const PATCH_GLYPH_RUN: u8 = 1;
+const PATCH_IMAGE: u8 = 2;
+
+const STYLE_FILL: u8 = 0;
+const STYLE_STROKE: u8 = 1;
+
+/// Errors that can occur when reading a serialized [`Encoding`].
+#[derive(Debug)]
+pub enum ReadError {
+    /// The underlying reader failed.
+    Io(io::Error),
+    /// The data doesn't start with the expected magic number.
+    BadMagic,
+    /// The data was written with an unsupported version of the format.
+    UnsupportedVersion(u32),
+    /// The data ended in the middle of a value.
+    Truncated,
+    /// A section required to reconstruct the encoding is missing.
+    MissingSection([u8; 4]),
+    /// The data is structurally invalid.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read encoding: {err}"),
+            Self::BadMagic => write!(f, "data is not a serialized encoding"),
+            Self::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported encoding format version {version} (expected at most {FORMAT_VERSION})"
+            ),
+            Self::Truncated => write!(f, "serialized encoding is truncated"),
+            Self::MissingSection(tag) => write!(
+                f,
+                "serialized encoding is missing the `{}` section",
+                String::from_utf8_lossy(tag)
+            ),
+            Self::Invalid(reason) => write!(f, "invalid serialized encoding: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Encoding {
+    /// Writes the encoding, including all late bound resources, in the
+    /// versioned binary container format.
+    ///
+    /// The optional [`GlyphRun::buffer`] is layout state rather than scene
+    /// data and is not serialized.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut blobs = BlobTable::default();
+        let sections = [
+            (SECTION_COUNTS, self.counts_section()),
+            (
+                SECTION_PATH_TAGS,
+                self.path_tags.iter().map(|tag| tag.0).collect(),
+            ),
+            (SECTION_PATH_DATA, words_section(&self.path_data)),
+            (
+                SECTION_DRAW_TAGS,
+                words_section(self.draw_tags.iter().map(|tag| &tag.0)),
+            ),
+            (SECTION_DRAW_DATA, words_section(&self.draw_data)),
+            (SECTION_TRANSFORMS, self.transforms_section()),
+            (SECTION_STYLES, self.styles_section()),
+            (SECTION_COLOR_STOPS, self.color_stops_section()),
+            (SECTION_PATCHES, self.patches_section(&mut blobs)),
+            (SECTION_GLYPHS, self.glyphs_section()),
+            (SECTION_GLYPH_RUNS, self.glyph_runs_section(&mut blobs)),
+            (
+                SECTION_NORMALIZED_COORDS,
+                self.resources
+                    .normalized_coords
+                    .iter()
+                    .flat_map(|coord| coord.to_le_bytes())
+                    .collect(),
+            ),
+            // Blobs are referenced by the sections above, so this must come last.
+            (SECTION_BLOBS, blobs.section()),
+        ];
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        writer.write_all(&(sections.len() as u32).to_le_bytes())?;
+        for (tag, payload) in &sections {
+            writer.write_all(tag)?;
+            writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+            writer.write_all(payload)?;
+        }
+        Ok(())
+    }
+
+    /// Reads an encoding previously written with [`Encoding::write_to`].
+    ///
+    /// Sections with unknown tags are skipped. Images and fonts that shared a
+    /// blob when written share a single blob after reading.
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, ReadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut header = ByteReader::new(&bytes);
+        if header.take(MAGIC.len()).map_err(|_| ReadError::BadMagic)? != MAGIC {
+            return Err(ReadError::BadMagic);
+        }
+        let version = header.u32()?;
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(ReadError::UnsupportedVersion(version));
+        }
+        let n_sections = header.u32()?;
+        let mut sections = HashMap::new();
+        for _ in 0..n_sections {
+            let tag: [u8; 4] = header.take(4)?.try_into().unwrap();
+            let len = header.len()?;
+            if sections.insert(tag, header.take(len)?).is_some() {
+                return Err(ReadError::Invalid("duplicate section"));
+            }
+        }
+        header.finish()?;
+        let section = |tag: [u8; 4]| {
+            sections
+                .get(&tag)
+                .map(|data| ByteReader::new(data))
+                .ok_or(ReadError::MissingSection(tag))
+        };
+
+        let blobs = read_blobs(section(SECTION_BLOBS)?)?;
+        let mut encoding = Self::new();
+        let mut counts = section(SECTION_COUNTS)?;
+        encoding.n_paths = counts.u32()?;
+        encoding.n_path_segments = counts.u32()?;
+        encoding.n_clips = counts.u32()?;
+        encoding.n_open_clips = counts.u32()?;
+        encoding.flags = counts.u32()?;
+        counts.finish()?;
+        encoding.path_tags = section(SECTION_PATH_TAGS)?
+            .rest()
+            .iter()
+            .map(|tag| PathTag(*tag))
+            .collect();
+        encoding.path_data = section(SECTION_PATH_DATA)?.records(ByteReader::u32)?;
+        encoding.draw_tags = section(SECTION_DRAW_TAGS)?.records(|r| r.u32().map(DrawTag))?;
+        encoding.draw_data = section(SECTION_DRAW_DATA)?.records(ByteReader::u32)?;
+        encoding.transforms = section(SECTION_TRANSFORMS)?.records(ByteReader::transform)?;
+        encoding.styles = section(SECTION_STYLES)?.records(|r| {
+            Ok(Style {
+                flags_and_miter_limit: r.u32()?,
+                line_width: r.f32()?,
+            })
+        })?;
+        let resources = &mut encoding.resources;
+        resources.color_stops = section(SECTION_COLOR_STOPS)?.records(ByteReader::color_stop)?;
+        resources.glyphs = section(SECTION_GLYPHS)?.records(|r| {
+            Ok(Glyph {
+                id: r.u32()?,
+                x: r.f32()?,
+                y: r.f32()?,
+            })
+        })?;
+        resources.normalized_coords =
+            section(SECTION_NORMALIZED_COORDS)?.records(ByteReader::i16)?;
+        resources.patches = section(SECTION_PATCHES)?.counted(|r| r.patch(&blobs))?;
+        resources.glyph_runs = section(SECTION_GLYPH_RUNS)?.counted(|r| r.glyph_run(&blobs))?;
+        // Resources index into the streams when resolving, so references
+        // outside of them must be rejected here rather than panic later.
+        encoding
+            .validate_resources()
+            .map_err(|_| ReadError::Invalid("resource refers to data outside the encoding"))?;
+        Ok(encoding)
+    }
+
+    fn counts_section(&self) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        w.u32(self.n_paths);
+        w.u32(self.n_path_segments);
+        w.u32(self.n_clips);
+        w.u32(self.n_open_clips);
+        w.u32(self.flags);
+        w.0
+    }
+
+    fn transforms_section(&self) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        for transform in &self.transforms {
+            w.transform(transform);
+        }
+        w.0
+    }
+
+    fn styles_section(&self) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        for style in &self.styles {
+            w.u32(style.flags_and_miter_limit);
+            w.f32(style.line_width);
+        }
+        w.0
+    }
+
+    fn color_stops_section(&self) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        for stop in &self.resources.color_stops {
+            w.f32(stop.offset);
+            w.color(&stop.color);
+        }
+        w.0
+    }
+
+    fn glyphs_section(&self) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        for glyph in &self.resources.glyphs {
+            w.u32(glyph.id);
+            w.f32(glyph.x);
+            w.f32(glyph.y);
+        }
+        w.0
+    }
+
+    fn patches_section<'a>(&'a self, blobs: &mut BlobTable<'a>) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        w.len(self.resources.patches.len());
+        for patch in &self.resources.patches {
+            match patch {
+                Patch::Ramp {
+                    draw_data_offset,
+                    stops,
+                    extend,
+                } => {
+                    w.u8(PATCH_RAMP);
+                    w.len(*draw_data_offset);
+                    w.range(stops);
+                    w.u8(*extend as u8);
+                }
+                Patch::GlyphRun { index } => {
+                    w.u8(PATCH_GLYPH_RUN);
+                    w.len(*index);
+                }
+                Patch::Image {
+                    draw_data_offset,
+                    image,
+                    alpha_multiplier,
+                } => {
+                    w.u8(PATCH_IMAGE);
+                    w.len(*draw_data_offset);
+                    w.f32(*alpha_multiplier);
+                    w.len(blobs.insert(&image.data));
+                    w.u8(image.format as u8);
+                    w.u32(image.width);
+                    w.u32(image.height);
+                    w.u8(image.x_extend as u8);
+                    w.u8(image.y_extend as u8);
+                    w.u8(image.quality as u8);
+                    w.f32(image.alpha);
+                }
+            }
+        }
+        w.0
+    }
+
+    fn glyph_runs_section<'a>(&'a self, blobs: &mut BlobTable<'a>) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        w.len(self.resources.glyph_runs.len());
+        for run in &self.resources.glyph_runs {
+            w.len(blobs.insert(&run.font.data));
+            w.u32(run.font.index);
+            w.transform(&run.transform);
+            w.optional_transform(run.glyph_transform.as_ref());
+            w.optional_transform(run.brush_transform.as_ref());
+            w.f32(run.font_size);
+            w.u8(run.hint as u8);
+            w.f32(run.embolden);
+            w.f32(run.skew);
+            w.range(&run.normalized_coords);
+            match &run.style {
+                peniko::Style::Fill(fill) => {
+                    w.u8(STYLE_FILL);
+                    w.u8(*fill as u8);
+                }
+                peniko::Style::Stroke(stroke) => {
+                    w.u8(STYLE_STROKE);
+                    w.stroke(stroke);
+                }
+            }
+            w.range(&run.glyphs);
+            let offsets = &run.stream_offsets;
+            for offset in [
+                offsets.path_tags,
+                offsets.path_data,
+                offsets.draw_tags,
+                offsets.draw_data,
+                offsets.transforms,
+                offsets.styles,
+            ] {
+                w.len(offset);
+            }
+        }
+        w.0
+    }
+}
+
+fn words_section<'a>(words: impl IntoIterator<Item = &'a u32>) -> Vec<u8> {
+    words
+        .into_iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
+fn read_blobs(r: ByteReader<'_>) -> Result<Vec<Blob<u8>>, ReadError> {
+    r.counted(|r| {
+        let len = r.len()?;
+        let data = r.take(len)?.to_vec();
+        Ok(Blob::new(Arc::new(data)))
+    })
+}
+
+/// Deduplicated set of blobs referenced by a serialized encoding.
+#[derive(Default)]
+struct BlobTable<'a> {
+    indices: HashMap<u64, usize>,
+    blobs: Vec<&'a [u8]>,
+}
+
+impl<'a> BlobTable<'a> {
+    fn insert(&mut self, blob: &'a Blob<u8>) -> usize {
+        match self.indices.entry(blob.id()) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                self.blobs.push(blob.data());
+                *entry.insert(self.blobs.len() - 1)
+            }
+        }
+    }
+
+    fn section(&self) -> Vec<u8> {
+        let mut w = ByteWriter::default();
+        w.len(self.blobs.len());
+        for blob in &self.blobs {
+            w.len(blob.len());
+            w.0.extend_from_slice(blob);
+        }
+        w.0
+    }
+}
+
+#[derive(Default)]
+struct ByteWriter(Vec<u8>);
+
+impl ByteWriter {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f64(&mut self, value: f64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, value: usize) {
+        self.0.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+
+    fn range(&mut self, range: &Range<usize>) {
+        self.len(range.start);
+        self.len(range.end);
+    }
+
+    fn transform(&mut self, transform: &Transform) {
+        for value in transform.matrix.iter().chain(&transform.translation) {
+            self.f32(*value);
+        }
+    }
+
+    fn optional_transform(&mut self, transform: Option<&Transform>) {
+        match transform {
+            Some(transform) => {
+                self.u8(1);
+                self.transform(transform);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn color(&mut self, color: &DynamicColor) {
+        self.u8(color.cs as u8);
+        let missing = color.flags.missing();
+        self.u8((0..4)
+            .filter(|ix| missing.contains(*ix))
+            .fold(0, |bits, ix| bits | 1 << ix));
+        for component in color.components {
+            self.f32(component);
+        }
+    }
+
+    fn stroke(&mut self, stroke: &Stroke) {
+        self.f64(stroke.width);
+        self.u8(match stroke.join {
+            Join::Bevel => 0,
+            Join::Miter => 1,
+            Join::Round => 2,
+        });
+        self.f64(stroke.miter_limit);
+        for cap in [stroke.start_cap, stroke.end_cap] {
+            self.u8(match cap {
+                Cap::Butt => 0,
+                Cap::Square => 1,
+                Cap::Round => 2,
+            });
+        }
+        self.f64(stroke.dash_offset);
+        self.len(stroke.dash_pattern.len());
+        for dash in &stroke.dash_pattern {
+            self.f64(*dash);
+        }
+    }
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ReadError> {
+        if self.0.len() < len {
+            return Err(ReadError::Truncated);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ReadError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.0)
+    }
+
+    /// Fails if any bytes are left over.
+    fn finish(self) -> Result<(), ReadError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ReadError::Invalid("unexpected trailing bytes"))
+        }
+    }
+
+    /// Reads fixed size records until the end of the section.
+    fn records<T>(
+        mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, ReadError>,
+    ) -> Result<Vec<T>, ReadError> {
+        let mut records = Vec::new();
+        while !self.0.is_empty() {
+            records.push(read(&mut self)?);
+        }
+        Ok(records)
+    }
+
+    /// Reads a record count followed by that many variable size records.
+    fn counted<T>(
+        mut self,
+        mut read: impl FnMut(&mut Self) -> Result<T, ReadError>,
+    ) -> Result<Vec<T>, ReadError> {
+        let count = self.len()?;
+        // Don't trust the count for preallocation; every record is at least one byte.
+        let mut records = Vec::with_capacity(count.min(self.0.len()));
+        for _ in 0..count {
+            records.push(read(&mut self)?);
+        }
+        self.finish()?;
+        Ok(records)
+    }
+
+    fn u8(&mut self) -> Result<u8, ReadError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ReadError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn i16(&mut self) -> Result<NormalizedCoord, ReadError> {
+        self.array().map(i16::from_le_bytes)
+    }
+
+    fn f32(&mut self) -> Result<f32, ReadError> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    fn f64(&mut self) -> Result<f64, ReadError> {
+        self.array().map(f64::from_le_bytes)
+    }
+
+    fn len(&mut self) -> Result<usize, ReadError> {
+        let len = self.array().map(u64::from_le_bytes)?;
+        usize::try_from(len).map_err(|_| ReadError::Invalid("length exceeds address space"))
+    }
+
+    fn range(&mut self) -> Result<Range<usize>, ReadError> {
+        let range = self.len()?..self.len()?;
+        if range.start > range.end {
+            return Err(ReadError::Invalid("inverted range"));
+        }
+        Ok(range)
+    }
+
+    fn enum_u8<T: bytemuck::CheckedBitPattern<Bits = u8>>(
+        &mut self,
+        what: &'static str,
+    ) -> Result<T, ReadError> {
+        bytemuck::checked::try_cast(self.u8()?).map_err(|_| ReadError::Invalid(what))
+    }
+
+    fn blob(&mut self, blobs: &[Blob<u8>]) -> Result<Blob<u8>, ReadError> {
+        blobs
+            .get(self.len()?)
+            .cloned()
+            .ok_or(ReadError::Invalid("blob index out of range"))
+    }
+
+    fn transform(&mut self) -> Result<Transform, ReadError> {
+        Ok(Transform {
+            matrix: [self.f32()?, self.f32()?, self.f32()?, self.f32()?],
+            translation: [self.f32()?, self.f32()?],
+        })
+    }
+
+    fn optional_transform(&mut self, error: &'static str) -> Result<Option<Transform>, ReadError> {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.transform()?)),
+            _ => Err(ReadError::Invalid(error)),
+        }
+    }
+
+    fn color_stop(&mut self) -> Result<ColorStop, ReadError> {
+        let offset = self.f32()?;
+        let cs: ColorSpaceTag = self.enum_u8("unknown color space")?;
+        let missing_bits = self.u8()?;
+        let mut missing = Missing::default();
+        for ix in (0..4).filter(|ix| missing_bits & (1 << ix) != 0) {
+            missing.insert(ix);
+        }
+        let components = [self.f32()?, self.f32()?, self.f32()?, self.f32()?];
+        Ok(ColorStop {
+            offset,
+            color: DynamicColor {
+                cs,
+                flags: Flags::from_missing(missing),
+                components,
+            },
+        })
+    }
+
+    fn stroke(&mut self) -> Result<Stroke, ReadError> {
+        let width = self.f64()?;
+        let join = match self.u8()? {
+            0 => Join::Bevel,
+            1 => Join::Miter,
+            2 => Join::Round,
+            _ => return Err(ReadError::Invalid("unknown stroke join")),
+        };
+        let miter_limit = self.f64()?;
+        let mut caps = [Cap::Butt; 2];
+        for cap in &mut caps {
+            *cap = match self.u8()? {
+                0 => Cap::Butt,
+                1 => Cap::Square,
+                2 => Cap::Round,
+                _ => return Err(ReadError::Invalid("unknown stroke cap")),
+            };
+        }
+        let dash_offset = self.f64()?;
+        let n_dashes = self.len()?;
+        let dashes = (0..n_dashes)
+            .map(|_| self.f64())
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Stroke::new(width)
+            .with_join(join)
+            .with_miter_limit(miter_limit)
+            .with_start_cap(caps[0])
+            .with_end_cap(caps[1])
+            .with_dashes(dash_offset, dashes))
+    }
+
+    fn patch(&mut self, blobs: &[Blob<u8>]) -> Result<Patch, ReadError> {
+        Ok(match self.u8()? {
+            PATCH_RAMP => Patch::Ramp {
+                draw_data_offset: self.len()?,
+                stops: self.range()?,
+                extend: self.enum_u8::<Extend>("unknown extend mode")?,
+            },
+            PATCH_GLYPH_RUN => Patch::GlyphRun { index: self.len()? },
+            PATCH_IMAGE => {
+                let draw_data_offset = self.len()?;
+                let alpha_multiplier = self.f32()?;
+                let data = self.blob(blobs)?;
+                let format: ImageFormat = self.enum_u8("unknown image format")?;
+                let mut image = Image::new(data, format, self.u32()?, self.u32()?);
+                image.x_extend = self.enum_u8::<Extend>("unknown extend mode")?;
+                image.y_extend = self.enum_u8::<Extend>("unknown extend mode")?;
+                image.quality = self.enum_u8::<ImageQuality>("unknown image quality")?;
+                image.alpha = self.f32()?;
+                Patch::Image {
+                    draw_data_offset,
+                    image,
+                    alpha_multiplier,
+                }
+            }
+            _ => return Err(ReadError::Invalid("unknown patch kind")),
+        })
+    }
+
+    fn glyph_run(&mut self, blobs: &[Blob<u8>]) -> Result<GlyphRun, ReadError> {
+        let font = Font::new(self.blob(blobs)?, self.u32()?);
+        let transform = self.transform()?;
+        let glyph_transform = self.optional_transform("invalid glyph transform flag")?;
+        let brush_transform = self.optional_transform("invalid brush transform flag")?;
+        let font_size = self.f32()?;
+        let hint = match self.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(ReadError::Invalid("invalid hint flag")),
+        };
+        let embolden = self.f32()?;
+        let skew = self.f32()?;
+        let normalized_coords = self.range()?;
+        let style = match self.u8()? {
+            STYLE_FILL => peniko::Style::Fill(self.enum_u8::<Fill>("unknown fill rule")?),
+            STYLE_STROKE => peniko::Style::Stroke(self.stroke()?),
+            _ => return Err(ReadError::Invalid("unknown glyph run style")),
+        };
+        let glyphs = self.range()?;
+        let stream_offsets = StreamOffsets {
+            path_tags: self.len()?,
+            path_data: self.len()?,
+            draw_tags: self.len()?,
+            draw_data: self.len()?,
+            transforms: self.len()?,
+            styles: self.len()?,
+        };
+        Ok(GlyphRun {
+            font,
+            transform,
+            glyph_transform,
+            brush_transform,
+            font_size,
+            hint,
+            embolden,
+            skew,
+            normalized_coords,
+            style,
+            glyphs,
+            stream_offsets,
+            buffer: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use peniko::color::palette;
+    use peniko::kurbo::{Affine, Rect, Stroke};
+    use peniko::{Blob, Color, ColorStop, Extend, Fill, Font, Gradient, Image, ImageFormat};
+
+    use super::{FORMAT_VERSION, ReadError};
+    use crate::{Encoding, Glyph, GlyphRun, Patch, Transform};
+
+    fn test_encoding() -> Encoding {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&Rect::new(0.0, 0.0, 10.0, 10.0), true);
+        encoding.encode_color(palette::css::RED);
+        encoding.encode_transform(Transform::from_kurbo(&Affine::rotate(0.5)));
+        assert!(encoding.encode_stroke_style(&Stroke::new(2.0).with_dashes(1.0, [3.0, 4.0])));
+        encoding.encode_shape(&Rect::new(5.0, 5.0, 20.0, 20.0), false);
+        let gradient = Gradient::new_linear((0.0, 0.0), (20.0, 0.0)).with_stops([
+            ColorStop::from((0.0, Color::from_rgba8(255, 0, 0, 255))),
+            ColorStop::from((1.0, Color::from_rgba8(0, 0, 255, 128))),
+        ]);
+        encoding.encode_brush(&gradient, 0.5);
+        let image = Image::new(
+            Blob::new(Arc::new(vec![0xff_u8; 16])),
+            ImageFormat::Rgba8,
+            2,
+            2,
+        )
+        .with_extend(Extend::Repeat);
+        for _ in 0..2 {
+            encoding.encode_fill_style(Fill::EvenOdd);
+            encoding.encode_shape(&Rect::new(0.0, 0.0, 2.0, 2.0), true);
+            encoding.encode_image(&image, 0.75);
+        }
+        let stream_offsets = encoding.stream_offsets();
+        encoding.resources.glyphs.extend([
+            Glyph {
+                id: 3,
+                x: 0.0,
+                y: 0.0,
+            },
+            Glyph {
+                id: 7,
+                x: 8.5,
+                y: 0.0,
+            },
+        ]);
+        encoding
+            .resources
+            .normalized_coords
+            .extend([-0x4000, 0x2000]);
+        encoding.resources.glyph_runs.push(GlyphRun {
+            font: Font::new(Blob::new(Arc::new(vec![1_u8, 2, 3])), 1),
+            transform: Transform::from_kurbo(&Affine::translate((4.0, 12.0))),
+            glyph_transform: Some(Transform::from_kurbo(&Affine::skew(0.2, 0.0))),
+            brush_transform: Some(Transform::from_kurbo(&Affine::scale(2.0))),
+            font_size: 12.0,
+            hint: true,
+            embolden: 0.25,
+            skew: 0.2,
+            normalized_coords: 0..2,
+            style: Stroke::new(0.5).into(),
+            glyphs: 0..2,
+            stream_offsets,
+            buffer: None,
+        });
+        encoding
+            .resources
+            .patches
+            .push(Patch::GlyphRun { index: 0 });
+        encoding.encode_color(palette::css::BLACK);
+        encoding.encode_begin_clip(peniko::Mix::Multiply.into(), 0.5);
+        encoding
+    }
+
+    fn to_bytes(encoding: &Encoding) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        encoding.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let encoding = test_encoding();
+        let bytes = to_bytes(&encoding);
+        let decoded = Encoding::read_from(&mut bytes.as_slice()).unwrap();
+        assert!(decoded.path_tags == encoding.path_tags);
+        assert_eq!(decoded.path_data, encoding.path_data);
+        assert!(decoded.draw_tags == encoding.draw_tags);
+        assert_eq!(decoded.draw_data, encoding.draw_data);
+        assert_eq!(decoded.transforms, encoding.transforms);
+        assert_eq!(decoded.styles, encoding.styles);
+        assert_eq!(decoded.n_paths, encoding.n_paths);
+        assert_eq!(decoded.n_open_clips, 1);
+        assert_eq!(
+            decoded.resources.color_stops,
+            encoding.resources.color_stops
+        );
+        let run = &decoded.resources.glyph_runs[0];
+        assert!(matches!(&run.style, peniko::Style::Stroke(stroke) if stroke.width == 0.5));
+        assert_eq!(run.font.index, 1);
+        assert_eq!(
+            run.brush_transform,
+            encoding.resources.glyph_runs[0].brush_transform
+        );
+        assert_eq!(run.font.data.data(), &[1, 2, 3]);
+        assert_eq!((run.embolden, run.skew), (0.25, 0.2));
+        // Writing the decoded encoding must reproduce the original bytes.
+        assert_eq!(to_bytes(&decoded), bytes);
+    }
+
+    #[test]
+    fn blobs_are_deduplicated() {
+        let encoding = test_encoding();
+        let decoded = Encoding::read_from(&mut to_bytes(&encoding).as_slice()).unwrap();
+        let images: Vec<_> = decoded
+            .resources
+            .patches
+            .iter()
+            .filter_map(|patch| match patch {
+                Patch::Image { image, .. } => Some(image),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].data.id(), images[1].data.id());
+        assert_eq!(images[0].x_extend, Extend::Repeat);
+        assert_eq!(images[0].data.data(), &[0xff_u8; 16]);
+    }
+
+    #[test]
+    fn empty_round_trip() {
+        let bytes = to_bytes(&Encoding::new());
+        let decoded = Encoding::read_from(&mut bytes.as_slice()).unwrap();
+        assert!(decoded.is_empty());
+        assert_eq!(decoded.stream_offsets().draw_data, 0);
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = to_bytes(&test_encoding());
+        for len in [10, 20, bytes.len() / 2, bytes.len() - 1] {
+            assert!(matches!(
+                Encoding::read_from(&mut &bytes[..len]),
+                Err(ReadError::Truncated)
+            ));
+        }
+        assert!(matches!(
+            Encoding::read_from(&mut &bytes[..4]),
+            Err(ReadError::BadMagic)
+        ));
+    }
+
+    #[test]
+    fn out_of_range_resources() {
+        let read = |encoding: &Encoding| Encoding::read_from(&mut to_bytes(encoding).as_slice());
+        let mut encoding = test_encoding();
+        let Some(Patch::Ramp {
+            draw_data_offset, ..
+        }) = encoding.resources.patches.first_mut()
+        else {
+            panic!("expected a ramp patch");
+        };
+        *draw_data_offset = 1_000;
+        assert!(matches!(read(&encoding), Err(ReadError::Invalid(_))));
+        let mut encoding = test_encoding();
+        encoding.resources.glyph_runs[0].normalized_coords = 1..5;
+        assert!(matches!(read(&encoding), Err(ReadError::Invalid(_))));
+        let mut encoding = test_encoding();
+        encoding.resources.glyph_runs[0].glyphs = 0..3;
+        assert!(matches!(read(&encoding), Err(ReadError::Invalid(_))));
+        // A draw object whose data is missing can't be patched either.
+        let mut encoding = test_encoding();
+        encoding.draw_data.truncate(encoding.draw_data.len() - 4);
+        assert!(matches!(read(&encoding), Err(ReadError::Invalid(_))));
+    }
+
+    #[test]
+    fn incompatible() {
+        let mut bytes = to_bytes(&test_encoding());
+        bytes[8..12].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            Encoding::read_from(&mut bytes.as_slice()),
+            Err(ReadError::UnsupportedVersion(v)) if v == FORMAT_VERSION + 1
+        ));
+        bytes[0] = b'X';
+        assert!(matches!(
+            Encoding::read_from(&mut bytes.as_slice()),
+            Err(ReadError::BadMagic)
+        ));
+    }
+}
@@ -0,0 +1,567 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Minimal TrueType fonts built in memory for tests.
+
+/// A point of a glyph contour in font units and whether it is on the curve.
+type Point = (i16, i16, bool);
+
+/// Builder for a TrueType font with simple `glyf` outlines.
+pub(crate) struct TestFont {
+    units_per_em: u16,
+    /// Contours of each glyph, indexed by glyph id.
+    glyphs: Vec<Vec<Vec<Point>>>,
+    /// Whether the font has a `wght` axis.
+    weight_axis: bool,
+    /// Whether the `wght` axis has a segment map in an `avar` table.
+    weight_avar: bool,
+    /// Whether the font has color glyph tables.
+    color_glyphs: bool,
+}
+
+impl TestFont {
+    /// Glyph without an outline.
+    pub(crate) const SPACE: u32 = 1;
+    /// Square covering `100..600` by `0..500` font units.
+    pub(crate) const SQUARE: u32 = 2;
+    /// Triangle with a quadratic top, covering `0..500` by `0..700` font
+    /// units including the off-curve point.
+    pub(crate) const TRIANGLE: u32 = 3;
+    /// `COLRv0` glyph with a red square and a triangle in the foreground
+    /// color.
+    pub(crate) const COLR_V0: u32 = 4;
+    /// `COLRv1` glyph clipped to `0..600` by `0..700` font units, with a
+    /// blue triangle multiplied onto a square filled with a red to blue
+    /// linear gradient.
+    pub(crate) const COLR_V1: u32 = 5;
+    /// Bitmap glyph with a 2x2 PNG in a 20 pixels per em `sbix` strike.
+    pub(crate) const BITMAP: u32 = 6;
+    /// `COLRv1` glyph with two layers: a red square and a blue paint that
+    /// isn't enclosed by a glyph.
+    pub(crate) const COLR_V1_LAYERS: u32 = 7;
+    /// Pixels of the bitmap glyph: red, green, blue and transparent.
+    pub(crate) const BITMAP_PIXELS: [u8; 16] =
+        [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 0, 0, 0, 0];
+
+    /// Family name of the font.
+    pub(crate) const FAMILY: &'static str = "Test";
+    /// Characters mapped to glyphs by the font, in ascending order.
+    pub(crate) const CHARS: [(char, u32); 3] = [
+        (' ', Self::SPACE),
+        ('a', Self::SQUARE),
+        ('b', Self::TRIANGLE),
+    ];
+
+    /// Creates a font with the glyphs listed above and an empty `.notdef`.
+    pub(crate) fn new(units_per_em: u16) -> Self {
+        let square = vec![vec![
+            (100, 0, true),
+            (600, 0, true),
+            (600, 500, true),
+            (100, 500, true),
+        ]];
+        let triangle = vec![vec![(0, 0, true), (500, 0, true), (250, 700, false)]];
+        Self {
+            units_per_em,
+            glyphs: vec![
+                vec![],
+                vec![],
+                square,
+                triangle,
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+            ],
+            weight_axis: false,
+            weight_avar: false,
+            color_glyphs: false,
+        }
+    }
+
+    /// Adds the `COLR`, `CPAL` and `sbix` tables of the color glyphs, which
+    /// otherwise have no outlines.
+    pub(crate) fn with_color_glyphs(mut self) -> Self {
+        self.color_glyphs = true;
+        self
+    }
+
+    /// Adds a `wght` axis ranging from 100 to 900 with a default of 400.
+    ///
+    /// The right edge of the square moves 200 font units to the right at the
+    /// maximum weight and is unaffected by lighter weights.
+    pub(crate) fn with_weight_axis(mut self) -> Self {
+        self.weight_axis = true;
+        self
+    }
+
+    /// Adds a `wght` axis like [`with_weight_axis`](Self::with_weight_axis)
+    /// with an `avar` table that maps the default normalized coordinate 0.5
+    /// to 0.25.
+    pub(crate) fn with_weight_avar(mut self) -> Self {
+        self.weight_axis = true;
+        self.weight_avar = true;
+        self
+    }
+
+    /// Serializes the font.
+    pub(crate) fn build(&self) -> Vec<u8> {
+        let n_glyphs = self.glyphs.len() as u16;
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for contours in &self.glyphs {
+            push_u32(&mut loca, glyf.len() as u32);
+            simple_glyph(&mut glyf, contours);
+        }
+        push_u32(&mut loca, glyf.len() as u32);
+
+        let mut head = Vec::new();
+        push_u32(&mut head, 0x0001_0000);
+        push_u32(&mut head, 0x0001_0000);
+        push_u32(&mut head, 0);
+        push_u32(&mut head, 0x5F0F_3CF5);
+        push_u16(&mut head, 0);
+        push_u16(&mut head, self.units_per_em);
+        head.extend([0; 16]);
+        for bound in [0, 0, 1000, 1000] {
+            push_u16(&mut head, bound);
+        }
+        push_u16(&mut head, 0);
+        push_u16(&mut head, 8);
+        push_u16(&mut head, 2);
+        // Long loca offsets.
+        push_u16(&mut head, 1);
+        push_u16(&mut head, 0);
+
+        let mut hhea = Vec::new();
+        push_u32(&mut hhea, 0x0001_0000);
+        for value in [800, (-200_i16) as u16, 0, 1000, 0, 0, 1000, 1, 0, 0] {
+            push_u16(&mut hhea, value);
+        }
+        hhea.extend([0; 8]);
+        push_u16(&mut hhea, 0);
+        push_u16(&mut hhea, n_glyphs);
+
+        let mut hmtx = Vec::new();
+        for _ in 0..n_glyphs {
+            push_u16(&mut hmtx, 700);
+            push_u16(&mut hmtx, 0);
+        }
+
+        let mut maxp = Vec::new();
+        push_u32(&mut maxp, 0x0000_5000);
+        push_u16(&mut maxp, n_glyphs);
+
+        let mut tables = vec![
+            (*b"cmap", cmap()),
+            (*b"glyf", glyf),
+            (*b"head", head),
+            (*b"hhea", hhea),
+            (*b"hmtx", hmtx),
+            (*b"loca", loca),
+            (*b"maxp", maxp),
+            (*b"name", name()),
+        ];
+        if self.weight_axis {
+            tables.push((*b"fvar", weight_fvar()));
+            tables.push((*b"gvar", self.weight_gvar()));
+        }
+        if self.weight_avar {
+            tables.push((*b"avar", weight_avar()));
+        }
+        if self.color_glyphs {
+            tables.push((*b"COLR", colr()));
+            tables.push((*b"CPAL", cpal()));
+            tables.push((*b"sbix", self.sbix()));
+        }
+        tables.sort_by_key(|table| table.0);
+        font_file(&tables)
+    }
+
+    /// Builds a bitmap table with a single strike containing the bitmap glyph.
+    fn sbix(&self) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 2, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&Self::BITMAP_PIXELS).unwrap();
+        writer.finish().unwrap();
+
+        let mut sbix = Vec::new();
+        push_u16(&mut sbix, 1);
+        push_u16(&mut sbix, 1);
+        push_u32(&mut sbix, 1);
+        push_u32(&mut sbix, 12);
+        // Strike at 20 pixels per em and 72 pixels per inch.
+        push_u16(&mut sbix, 20);
+        push_u16(&mut sbix, 72);
+        let data_start = 4 + 4 * (self.glyphs.len() as u32 + 1);
+        for id in 0..=self.glyphs.len() as u32 {
+            let offset = if id > Self::BITMAP {
+                data_start + 8 + png.len() as u32
+            } else {
+                data_start
+            };
+            push_u32(&mut sbix, offset);
+        }
+        // No origin offset.
+        push_u32(&mut sbix, 0);
+        sbix.extend(b"png ");
+        sbix.extend(png);
+        sbix
+    }
+
+    /// Builds glyph variations with a single tuple peaking at the maximum
+    /// weight.
+    fn weight_gvar(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut offsets = Vec::new();
+        for (id, contours) in self.glyphs.iter().enumerate() {
+            push_u32(&mut offsets, data.len() as u32);
+            if id as u32 != Self::SQUARE {
+                continue;
+            }
+            // Deltas cover the outline points followed by the four phantom
+            // points.
+            let n_points = contours.iter().map(Vec::len).sum::<usize>() + 4;
+            let x_deltas = contours
+                .iter()
+                .flatten()
+                .map(|point| if point.0 == 600 { 200 } else { 0 })
+                .chain([0; 4]);
+            // One tuple with an embedded peak and private point numbers.
+            push_u16(&mut data, 1);
+            push_u16(&mut data, 10);
+            let size_offset = data.len();
+            push_u16(&mut data, 0);
+            push_u16(&mut data, 0xA000);
+            push_u16(&mut data, 0x4000);
+            let start = data.len();
+            // All points.
+            data.push(0);
+            // A run of word sized x deltas and a run of zero y deltas.
+            data.push(0x40 | (n_points - 1) as u8);
+            for delta in x_deltas {
+                push_u16(&mut data, delta as u16);
+            }
+            data.push(0x80 | (n_points - 1) as u8);
+            let size = (data.len() - start) as u16;
+            data[size_offset..size_offset + 2].copy_from_slice(&size.to_be_bytes());
+            data.resize(data.len().next_multiple_of(2), 0);
+        }
+        push_u32(&mut offsets, data.len() as u32);
+
+        let mut gvar = Vec::new();
+        push_u32(&mut gvar, 0x0001_0000);
+        // One axis and no shared tuples.
+        push_u16(&mut gvar, 1);
+        push_u16(&mut gvar, 0);
+        let data_offset = 20 + offsets.len() as u32;
+        push_u32(&mut gvar, data_offset);
+        push_u16(&mut gvar, self.glyphs.len() as u16);
+        // Long offsets.
+        push_u16(&mut gvar, 1);
+        push_u32(&mut gvar, data_offset);
+        gvar.extend(offsets);
+        gvar.extend(data);
+        gvar
+    }
+}
+
+/// Builds a format 4 character map for [`TestFont::CHARS`] with one segment
+/// per character.
+fn cmap() -> Vec<u8> {
+    let chars = TestFont::CHARS.map(|(c, id)| (c as u16, id as u16));
+    // Each character has its own segment, followed by the final one.
+    let n_segments = chars.len() as u16 + 1;
+    let entry_selector = n_segments.ilog2() as u16;
+    let search_range = 2 << entry_selector;
+    let mut subtable = Vec::new();
+    for value in [
+        4,
+        16 + 8 * n_segments,
+        0,
+        2 * n_segments,
+        search_range,
+        entry_selector,
+        2 * n_segments - search_range,
+    ] {
+        push_u16(&mut subtable, value);
+    }
+    for (c, _) in chars {
+        push_u16(&mut subtable, c);
+    }
+    push_u16(&mut subtable, 0xFFFF);
+    push_u16(&mut subtable, 0);
+    for (c, _) in chars {
+        push_u16(&mut subtable, c);
+    }
+    push_u16(&mut subtable, 0xFFFF);
+    for (c, id) in chars {
+        push_u16(&mut subtable, id.wrapping_sub(c));
+    }
+    push_u16(&mut subtable, 1);
+    // No glyph id arrays.
+    subtable.extend([0; 2].repeat(n_segments as usize));
+
+    let mut cmap = Vec::new();
+    // One Windows Unicode BMP encoding record.
+    for value in [0, 1, 3, 1] {
+        push_u16(&mut cmap, value);
+    }
+    push_u32(&mut cmap, 12);
+    cmap.extend(subtable);
+    cmap
+}
+
+/// Builds a naming table with the family and PostScript names.
+fn name() -> Vec<u8> {
+    let family = TestFont::FAMILY
+        .encode_utf16()
+        .flat_map(u16::to_be_bytes)
+        .collect::<Vec<_>>();
+    let mut name = Vec::new();
+    for value in [0, 2, 6 + 2 * 12] {
+        push_u16(&mut name, value);
+    }
+    // Both names are stored as the same Windows English string.
+    for name_id in [1, 6] {
+        for value in [3, 1, 0x0409, name_id, family.len() as u16, 0] {
+            push_u16(&mut name, value);
+        }
+    }
+    name.extend(family);
+    name
+}
+
+/// Builds a palette with red at index 0 and blue at index 1.
+fn cpal() -> Vec<u8> {
+    let mut cpal = Vec::new();
+    // Two entries in one palette.
+    for value in [0, 2, 1, 2] {
+        push_u16(&mut cpal, value);
+    }
+    push_u32(&mut cpal, 14);
+    push_u16(&mut cpal, 0);
+    // Colors are stored as BGRA.
+    cpal.extend([0, 0, 255, 255, 255, 0, 0, 255]);
+    cpal
+}
+
+/// Builds a version 1 color table with the layers of
+/// [`TestFont::COLR_V0`] and the paint graphs of [`TestFont::COLR_V1`] and
+/// [`TestFont::COLR_V1_LAYERS`].
+fn colr() -> Vec<u8> {
+    const HEADER_SIZE: u32 = 34;
+    let mut colr = Vec::new();
+    push_u16(&mut colr, 1);
+    push_u16(&mut colr, 1);
+    push_u32(&mut colr, HEADER_SIZE);
+    push_u32(&mut colr, HEADER_SIZE + 6);
+    push_u16(&mut colr, 2);
+    let base_glyph_list = HEADER_SIZE + 6 + 8;
+    push_u32(&mut colr, base_glyph_list);
+    let layer_list_offset = colr.len();
+    push_u32(&mut colr, 0);
+    let clip_list_offset = colr.len();
+    push_u32(&mut colr, 0);
+    // No variations.
+    push_u32(&mut colr, 0);
+    push_u32(&mut colr, 0);
+
+    // Version 0 base glyph and its layers, the second of which uses the
+    // foreground color.
+    for value in [TestFont::COLR_V0 as u16, 0, 2] {
+        push_u16(&mut colr, value);
+    }
+    for value in [
+        TestFont::SQUARE as u16,
+        0,
+        TestFont::TRIANGLE as u16,
+        0xFFFF,
+    ] {
+        push_u16(&mut colr, value);
+    }
+
+    // Base glyph list with two paint graphs. Paint offsets are relative to
+    // the start of the referencing paint.
+    let mut list = Vec::new();
+    push_u32(&mut list, 2);
+    push_u16(&mut list, TestFont::COLR_V1 as u16);
+    push_u32(&mut list, 16);
+    push_u16(&mut list, TestFont::COLR_V1_LAYERS as u16);
+    let layers_offset = list.len();
+    push_u32(&mut list, 0);
+    // PaintComposite with a multiplied source over the backdrop.
+    list.push(32);
+    push_u24(&mut list, 8);
+    list.push(23);
+    push_u24(&mut list, 19);
+    // PaintGlyph of the triangle with a PaintSolid of blue.
+    list.push(10);
+    push_u24(&mut list, 6);
+    push_u16(&mut list, TestFont::TRIANGLE as u16);
+    list.push(2);
+    push_u16(&mut list, 1);
+    push_u16(&mut list, 0x4000);
+    // PaintGlyph of the square with a PaintLinearGradient.
+    list.push(10);
+    push_u24(&mut list, 6);
+    push_u16(&mut list, TestFont::SQUARE as u16);
+    list.push(4);
+    push_u24(&mut list, 16);
+    for value in [100, 0, 600, 0, 100, 500] {
+        push_u16(&mut list, value);
+    }
+    // Color line from red to blue with the default extend mode.
+    list.push(0);
+    push_u16(&mut list, 2);
+    for (offset, index) in [(0, 0), (0x4000, 1)] {
+        push_u16(&mut list, offset);
+        push_u16(&mut list, index);
+        push_u16(&mut list, 0x4000);
+    }
+    // PaintColrLayers with both layers of the layer list.
+    let layers = list.len() as u32;
+    list[layers_offset..layers_offset + 4].copy_from_slice(&layers.to_be_bytes());
+    list.push(1);
+    list.push(2);
+    push_u32(&mut list, 0);
+    colr.extend(list);
+
+    // Layer list with a PaintGlyph of the square with a PaintSolid of red,
+    // followed by a PaintSolid of blue.
+    let layer_list = colr.len() as u32;
+    colr[layer_list_offset..layer_list_offset + 4].copy_from_slice(&layer_list.to_be_bytes());
+    push_u32(&mut colr, 2);
+    push_u32(&mut colr, 12);
+    push_u32(&mut colr, 23);
+    colr.push(10);
+    push_u24(&mut colr, 6);
+    push_u16(&mut colr, TestFont::SQUARE as u16);
+    for (format, index) in [(2, 0), (2, 1)] {
+        colr.push(format);
+        push_u16(&mut colr, index);
+        push_u16(&mut colr, 0x4000);
+    }
+
+    let clip_list = colr.len() as u32;
+    colr[clip_list_offset..clip_list_offset + 4].copy_from_slice(&clip_list.to_be_bytes());
+    colr.push(1);
+    push_u32(&mut colr, 1);
+    push_u16(&mut colr, TestFont::COLR_V1 as u16);
+    push_u16(&mut colr, TestFont::COLR_V1 as u16);
+    push_u24(&mut colr, 12);
+    colr.push(1);
+    for value in [0, 0, 600, 700] {
+        push_u16(&mut colr, value);
+    }
+    colr
+}
+
+/// Builds font variations with a `wght` axis from 100 to 900.
+fn weight_fvar() -> Vec<u8> {
+    let mut fvar = Vec::new();
+    push_u32(&mut fvar, 0x0001_0000);
+    // Axes offset, reserved, one axis of 20 bytes and no instances.
+    for value in [16, 2, 1, 20, 0, 8] {
+        push_u16(&mut fvar, value);
+    }
+    fvar.extend(b"wght");
+    for value in [100, 400, 900] {
+        push_u32(&mut fvar, value << 16);
+    }
+    push_u16(&mut fvar, 0);
+    push_u16(&mut fvar, 256);
+    fvar
+}
+
+/// Builds axis variations with a single segment map for the `wght` axis.
+fn weight_avar() -> Vec<u8> {
+    let mut avar = Vec::new();
+    push_u32(&mut avar, 0x0001_0000);
+    // Reserved and one axis.
+    push_u16(&mut avar, 0);
+    push_u16(&mut avar, 1);
+    let map: [(i16, i16); 4] = [
+        (-0x4000, -0x4000),
+        (0, 0),
+        (0x2000, 0x1000),
+        (0x4000, 0x4000),
+    ];
+    push_u16(&mut avar, map.len() as u16);
+    for (from, to) in map {
+        push_u16(&mut avar, from as u16);
+        push_u16(&mut avar, to as u16);
+    }
+    avar
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend(value.to_be_bytes());
+}
+
+fn push_u24(data: &mut Vec<u8>, value: u32) {
+    data.extend(&value.to_be_bytes()[1..]);
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend(value.to_be_bytes());
+}
+
+/// Appends a simple glyph with uncompressed flags and coordinates.
+fn simple_glyph(glyf: &mut Vec<u8>, contours: &[Vec<Point>]) {
+    if contours.is_empty() {
+        return;
+    }
+    let points = || contours.iter().flatten();
+    push_u16(glyf, contours.len() as u16);
+    let xs = || points().map(|point| point.0);
+    let ys = || points().map(|point| point.1);
+    for bound in [
+        xs().min().unwrap(),
+        ys().min().unwrap(),
+        xs().max().unwrap(),
+        ys().max().unwrap(),
+    ] {
+        push_u16(glyf, bound as u16);
+    }
+    let mut end = 0;
+    for contour in contours {
+        end += contour.len();
+        push_u16(glyf, end as u16 - 1);
+    }
+    // No instructions.
+    push_u16(glyf, 0);
+    glyf.extend(points().map(|point| u8::from(point.2)));
+    for coords in [xs().collect::<Vec<_>>(), ys().collect::<Vec<_>>()] {
+        let mut prev = 0;
+        for coord in coords {
+            push_u16(glyf, coord.wrapping_sub(prev) as u16);
+            prev = coord;
+        }
+    }
+    glyf.resize(glyf.len().next_multiple_of(4), 0);
+}
+
+/// Assembles the table directory and the tables, which must be sorted by
+/// tag. Checksums are left empty.
+fn font_file(tables: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut data = Vec::new();
+    push_u32(&mut data, 0x0001_0000);
+    push_u16(&mut data, tables.len() as u16);
+    data.extend([0; 6]);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, table) in tables {
+        data.extend(tag);
+        push_u32(&mut data, 0);
+        push_u32(&mut data, offset as u32);
+        push_u32(&mut data, table.len() as u32);
+        offset += table.len().next_multiple_of(4);
+    }
+    for (_, table) in tables {
+        data.extend(table);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+    data
+}
@@ -0,0 +1,176 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Encoding of text shaped by glyphon.
+
+use glyphon::{Buffer, fontdb};
+use peniko::kurbo::{Affine, Point};
+use peniko::{BrushRef, Color, Fill, Font};
+
+use super::{Encoding, Glyph, Transform};
+
+impl Encoding {
+    /// Encodes the shaped and laid out text of a glyphon buffer with its top
+    /// left corner at `origin`.
+    ///
+    /// Every span of consecutive glyphs in a layout run that share a font,
+    /// size and color becomes a glyph run, positioned exactly as laid out by
+    /// the buffer. Glyphs without a color of their own are drawn with
+    /// `default_brush`.
+    ///
+    /// `fonts` maps the font identifiers of the buffer to fonts. It should
+    /// return the same font blob for an identifier on every call, since the
+    /// [`Resolver`](crate::Resolver) caches outlines per blob. Spans with
+    /// unknown fonts are skipped.
+    ///
+    /// Returns the number of skipped spans.
+    #[expect(
+        single_use_lifetimes,
+        reason = "False positive: https://github.com/rust-lang/rust/issues/129255"
+    )]
+    pub fn encode_text_buffer<'b>(
+        &mut self,
+        buffer: &Buffer,
+        origin: Point,
+        default_brush: impl Into<BrushRef<'b>>,
+        mut fonts: impl FnMut(fontdb::ID) -> Option<Font>,
+    ) -> usize {
+        let default_brush = default_brush.into();
+        let transform = Transform::from_kurbo(&Affine::translate(origin.to_vec2()));
+        let mut n_skipped = 0;
+        for run in buffer.layout_runs() {
+            let spans = run.glyphs.chunk_by(|a, b| {
+                a.font_id == b.font_id && a.font_size == b.font_size && a.color_opt == b.color_opt
+            });
+            for span in spans {
+                let first = &span[0];
+                let Some(font) = fonts(first.font_id) else {
+                    n_skipped += 1;
+                    continue;
+                };
+                let brush = match first.color_opt {
+                    Some(color) => BrushRef::Solid(Color::from_rgba8(
+                        color.r(),
+                        color.g(),
+                        color.b(),
+                        color.a(),
+                    )),
+                    None => default_brush,
+                };
+                // Offsets are in ems and the y offset points up.
+                let glyphs = span.iter().map(|glyph| Glyph {
+                    id: glyph.glyph_id.into(),
+                    x: glyph.x + glyph.x_offset * glyph.font_size,
+                    y: run.line_y + glyph.y - glyph.y_offset * glyph.font_size,
+                });
+                self.encode_glyph_run(
+                    &font,
+                    first.font_size,
+                    transform,
+                    None,
+                    Fill::NonZero,
+                    brush,
+                    false,
+                    &[],
+                )
+                .glyphs(glyphs);
+            }
+        }
+        n_skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glyphon::{Attrs, Buffer, Family, FontSystem, Metrics, Shaping, fontdb};
+    use peniko::kurbo::Point;
+    use peniko::{Blob, Color, Font};
+
+    use crate::test_font::TestFont;
+    use crate::{DrawColor, Encoding};
+
+    #[test]
+    fn text_buffer_spans() {
+        let mut db = fontdb::Database::new();
+        db.load_font_data(TestFont::new(1000).build());
+        let mut font_system = FontSystem::new_with_locale_and_db("en-US".into(), db);
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(10.0, 12.0));
+        let attrs = Attrs::new().family(Family::Name(TestFont::FAMILY));
+        let red = glyphon::Color::rgb(255, 0, 0);
+        buffer.set_rich_text(
+            &mut font_system,
+            [("ab", attrs.clone().color(red)), (" a\nb", attrs.clone())],
+            &attrs,
+            Shaping::Advanced,
+            None,
+        );
+        buffer.shape_until_scroll(&mut font_system, false);
+
+        let mut encoding = Encoding::new();
+        let mut lookups = 0;
+        let skipped =
+            encoding.encode_text_buffer(&buffer, Point::new(5.0, 5.0), Color::WHITE, |id| {
+                lookups += 1;
+                font_system.db().with_face_data(id, |data, index| {
+                    Font::new(Blob::from(data.to_vec()), index)
+                })
+            });
+        let resources = &encoding.resources;
+        // A red and a default span on the first line and one on the second.
+        assert_eq!(lookups, 3);
+        assert_eq!(skipped, 0);
+        assert_eq!(resources.glyph_runs.len(), 3);
+        assert_eq!(resources.glyph_runs[0].glyphs, 0..2);
+        assert_eq!(resources.glyph_runs[1].glyphs, 2..4);
+        assert_eq!(resources.glyph_runs[0].transform.translation, [5.0, 5.0]);
+        assert_eq!(resources.glyph_runs[0].font_size, 10.0);
+        let ids = resources
+            .glyphs
+            .iter()
+            .map(|glyph| glyph.id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                TestFont::SQUARE,
+                TestFont::TRIANGLE,
+                TestFont::SPACE,
+                TestFont::SQUARE,
+                TestFont::TRIANGLE
+            ]
+        );
+        // Advances of 700 font units at 10 pixels per em, on the baseline
+        // of each line.
+        let lines = buffer
+            .layout_runs()
+            .map(|run| run.line_y)
+            .collect::<Vec<_>>();
+        let positions = resources
+            .glyphs
+            .iter()
+            .map(|glyph| (glyph.x, glyph.y))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            [
+                (0.0, lines[0]),
+                (7.0, lines[0]),
+                (14.0, lines[0]),
+                (21.0, lines[0]),
+                (0.0, lines[1])
+            ]
+        );
+        let rgba = |color: Color| DrawColor::from(color).rgba;
+        let white = rgba(Color::WHITE);
+        assert_eq!(
+            encoding.draw_data,
+            [rgba(Color::from_rgb8(255, 0, 0)), white, white]
+        );
+
+        // Spans with unknown fonts are reported instead of encoded.
+        let mut encoding = Encoding::new();
+        let skipped = encoding.encode_text_buffer(&buffer, Point::ZERO, Color::WHITE, |_| None);
+        assert_eq!(skipped, 3);
+        assert!(encoding.resources.glyph_runs.is_empty());
+    }
+}
@@ -0,0 +1,514 @@
+// Copyright 2025 the Vello Authors
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+
+//! Consistency checks for [`Encoding`] streams.
+
+use std::fmt;
+
+use super::{DrawTag, Encoding, Patch, PathTag};
+
+/// An invariant violated by an [`Encoding`].
+///
+/// Indices refer to positions in the stream named by the variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EncodingError {
+    /// A path segment or path marker was encoded before any transform.
+    MissingTransform {
+        /// Index of the offending tag in the path tag stream.
+        path_tag: usize,
+    },
+    /// A path segment or path marker was encoded before any style.
+    MissingStyle {
+        /// Index of the offending tag in the path tag stream.
+        path_tag: usize,
+    },
+    /// The path data stream doesn't contain the number of words implied by
+    /// the path tags.
+    PathDataLength {
+        /// Length in words implied by the path tag stream.
+        expected: usize,
+        /// Actual length of the path data stream.
+        actual: usize,
+    },
+    /// A path marker was encoded in the middle of a subpath.
+    UnterminatedSubpath {
+        /// Index of the path marker in the path tag stream.
+        path_tag: usize,
+    },
+    /// The transform stream doesn't match the number of transform tags.
+    TransformCount {
+        /// Number of [`PathTag::TRANSFORM`] tags.
+        expected: usize,
+        /// Actual length of the transform stream.
+        actual: usize,
+    },
+    /// The style stream doesn't match the number of style tags.
+    StyleCount {
+        /// Number of [`PathTag::STYLE`] tags.
+        expected: usize,
+        /// Actual length of the style stream.
+        actual: usize,
+    },
+    /// The draw data stream doesn't match the scene sizes of the draw tags.
+    DrawDataLength {
+        /// Length in words implied by the draw tag stream.
+        expected: usize,
+        /// Actual length of the draw data stream.
+        actual: usize,
+    },
+    /// The number of draw objects that need a path doesn't match the number
+    /// of encoded paths.
+    DrawPathMismatch {
+        /// Number of draw objects, excluding those produced by glyph runs.
+        draw_objects: usize,
+        /// Number of [`PathTag::PATH`] tags.
+        paths: usize,
+    },
+    /// [`Encoding::n_paths`] doesn't match the path tag stream.
+    PathCount {
+        /// Number of [`PathTag::PATH`] tags.
+        expected: u32,
+        /// Value of the counter.
+        actual: u32,
+    },
+    /// [`Encoding::n_path_segments`] doesn't match the path tag stream.
+    SegmentCount {
+        /// Number of segment tags.
+        expected: u32,
+        /// Value of the counter.
+        actual: u32,
+    },
+    /// [`Encoding::n_clips`] doesn't match the draw tag stream.
+    ClipCount {
+        /// Number of clip tags.
+        expected: u32,
+        /// Value of the counter.
+        actual: u32,
+    },
+    /// [`Encoding::n_open_clips`] doesn't match the draw tag stream.
+    OpenClipCount {
+        /// Number of begin clips without a matching end clip.
+        expected: u32,
+        /// Value of the counter.
+        actual: u32,
+    },
+    /// An end clip was encoded without a matching begin clip.
+    UnbalancedClip {
+        /// Index of the end clip in the draw tag stream.
+        draw_tag: usize,
+    },
+    /// A patch refers to data outside of its stream or to a draw object of
+    /// the wrong kind.
+    PatchOutOfRange {
+        /// Index of the patch in [`Resources::patches`](crate::Resources::patches).
+        patch: usize,
+    },
+    /// A glyph run refers to glyphs, coordinates or stream offsets outside of
+    /// the encoding.
+    GlyphRunOutOfRange {
+        /// Index of the run in [`Resources::glyph_runs`](crate::Resources::glyph_runs).
+        glyph_run: usize,
+    },
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingTransform { path_tag } => {
+                write!(f, "path tag {path_tag} precedes the first transform")
+            }
+            Self::MissingStyle { path_tag } => {
+                write!(f, "path tag {path_tag} precedes the first style")
+            }
+            Self::PathDataLength { expected, actual } => write!(
+                f,
+                "path tags require {expected} words of path data, found {actual}"
+            ),
+            Self::UnterminatedSubpath { path_tag } => {
+                write!(f, "path marker {path_tag} ends a path with an open subpath")
+            }
+            Self::TransformCount { expected, actual } => {
+                write!(f, "path tags require {expected} transforms, found {actual}")
+            }
+            Self::StyleCount { expected, actual } => {
+                write!(f, "path tags require {expected} styles, found {actual}")
+            }
+            Self::DrawDataLength { expected, actual } => write!(
+                f,
+                "draw tags require {expected} words of draw data, found {actual}"
+            ),
+            Self::DrawPathMismatch {
+                draw_objects,
+                paths,
+            } => write!(
+                f,
+                "{draw_objects} draw objects require paths, found {paths}"
+            ),
+            Self::PathCount { expected, actual } => {
+                write!(f, "path count is {actual}, expected {expected}")
+            }
+            Self::SegmentCount { expected, actual } => {
+                write!(f, "path segment count is {actual}, expected {expected}")
+            }
+            Self::ClipCount { expected, actual } => {
+                write!(f, "clip count is {actual}, expected {expected}")
+            }
+            Self::OpenClipCount { expected, actual } => {
+                write!(f, "open clip count is {actual}, expected {expected}")
+            }
+            Self::UnbalancedClip { draw_tag } => {
+                write!(
+                    f,
+                    "end clip at draw tag {draw_tag} has no matching begin clip"
+                )
+            }
+            Self::PatchOutOfRange { patch } => write!(f, "patch {patch} is out of range"),
+            Self::GlyphRunOutOfRange { glyph_run } => {
+                write!(f, "glyph run {glyph_run} is out of range")
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
+impl Encoding {
+    /// Checks the invariants that the GPU pipeline and [`Resolver`](crate::Resolver)
+    /// rely on, returning the first violation found.
+    ///
+    /// This walks every stream, so it is intended for debugging and for
+    /// encodings from untrusted sources rather than for every frame.
+    pub fn validate(&self) -> Result<(), EncodingError> {
+        self.validate_path_streams()?;
+        self.validate_draw_streams()?;
+        self.validate_resources()
+    }
+
+    fn validate_path_streams(&self) -> Result<(), EncodingError> {
+        let mut n_transforms = 0;
+        let mut n_styles = 0;
+        let mut n_paths = 0_u32;
+        let mut n_segments = 0_u32;
+        let mut path_data_len = 0;
+        let mut in_subpath = false;
+        for (ix, tag) in self.path_tags.iter().enumerate() {
+            match *tag {
+                PathTag::TRANSFORM => n_transforms += 1,
+                PathTag::STYLE => n_styles += 1,
+                tag if tag == PathTag::PATH || tag.is_path_segment() => {
+                    if n_transforms == 0 {
+                        return Err(EncodingError::MissingTransform { path_tag: ix });
+                    }
+                    if n_styles == 0 {
+                        return Err(EncodingError::MissingStyle { path_tag: ix });
+                    }
+                    if tag == PathTag::PATH {
+                        if in_subpath {
+                            return Err(EncodingError::UnterminatedSubpath { path_tag: ix });
+                        }
+                        n_paths += 1;
+                    } else {
+                        n_segments += 1;
+                        path_data_len += tag.data_size();
+                        in_subpath = !tag.is_subpath_end();
+                    }
+                }
+                _ => {}
+            }
+        }
+        if path_data_len != self.path_data.len() {
+            return Err(EncodingError::PathDataLength {
+                expected: path_data_len,
+                actual: self.path_data.len(),
+            });
+        }
+        if n_transforms != self.transforms.len() {
+            return Err(EncodingError::TransformCount {
+                expected: n_transforms,
+                actual: self.transforms.len(),
+            });
+        }
+        if n_styles != self.styles.len() {
+            return Err(EncodingError::StyleCount {
+                expected: n_styles,
+                actual: self.styles.len(),
+            });
+        }
+        if n_paths != self.n_paths {
+            return Err(EncodingError::PathCount {
+                expected: n_paths,
+                actual: self.n_paths,
+            });
+        }
+        if n_segments != self.n_path_segments {
+            return Err(EncodingError::SegmentCount {
+                expected: n_segments,
+                actual: self.n_path_segments,
+            });
+        }
+        Ok(())
+    }
+
+    fn validate_draw_streams(&self) -> Result<(), EncodingError> {
+        let mut draw_data_len = 0;
+        let mut n_draw_objects = 0;
+        let mut n_clips = 0_u32;
+        let mut n_open_clips = 0_u32;
+        for (ix, tag) in self.draw_tags.iter().enumerate() {
+            draw_data_len += tag.scene_size() as usize;
+            if *tag != DrawTag::NOP {
+                n_draw_objects += 1;
+            }
+            match *tag {
+                DrawTag::BEGIN_CLIP => {
+                    n_clips += 1;
+                    n_open_clips += 1;
+                }
+                DrawTag::END_CLIP => {
+                    if n_open_clips == 0 {
+                        return Err(EncodingError::UnbalancedClip { draw_tag: ix });
+                    }
+                    n_clips += 1;
+                    n_open_clips -= 1;
+                }
+                _ => {}
+            }
+        }
+        if draw_data_len != self.draw_data.len() {
+            return Err(EncodingError::DrawDataLength {
+                expected: draw_data_len,
+                actual: self.draw_data.len(),
+            });
+        }
+        // Glyph runs have a draw object but their paths are only inserted by
+        // the resolver.
+        let draw_objects = n_draw_objects - self.resources.glyph_runs.len().min(n_draw_objects);
+        let paths = self.n_paths as usize;
+        if draw_objects != paths {
+            return Err(EncodingError::DrawPathMismatch {
+                draw_objects,
+                paths,
+            });
+        }
+        if n_clips != self.n_clips {
+            return Err(EncodingError::ClipCount {
+                expected: n_clips,
+                actual: self.n_clips,
+            });
+        }
+        if n_open_clips != self.n_open_clips {
+            return Err(EncodingError::OpenClipCount {
+                expected: n_open_clips,
+                actual: self.n_open_clips,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that patches and glyph runs only refer to data inside the
+    /// encoding, which is what the resolver indexes with them.
+    ///
+    /// Unlike the other checks, this doesn't assume that the streams are
+    /// consistent with each other.
+    pub(crate) fn validate_resources(&self) -> Result<(), EncodingError> {
+        let resources = &self.resources;
+        // Draw data offset of every draw object whose data is present, paired
+        // with its tag.
+        let mut draw_objects = Vec::with_capacity(self.draw_tags.len());
+        let mut offset = 0;
+        for tag in &self.draw_tags {
+            let end = offset + tag.scene_size() as usize;
+            if end > self.draw_data.len() {
+                break;
+            }
+            draw_objects.push((offset, *tag));
+            offset = end;
+        }
+        let draw_object_at = |offset: usize| {
+            draw_objects
+                .binary_search_by_key(&offset, |(offset, _)| *offset)
+                .ok()
+                .map(|ix| draw_objects[ix].1)
+        };
+        for (ix, patch) in resources.patches.iter().enumerate() {
+            let in_range = match patch {
+                Patch::Ramp {
+                    draw_data_offset,
+                    stops,
+                    ..
+                } => {
+                    matches!(
+                        draw_object_at(*draw_data_offset),
+                        Some(
+                            DrawTag::LINEAR_GRADIENT
+                                | DrawTag::RADIAL_GRADIENT
+                                | DrawTag::SWEEP_GRADIENT
+                        )
+                    ) && stops.start < stops.end
+                        && stops.end <= resources.color_stops.len()
+                }
+                Patch::GlyphRun { index } => *index < resources.glyph_runs.len(),
+                Patch::Image {
+                    draw_data_offset, ..
+                } => draw_object_at(*draw_data_offset) == Some(DrawTag::IMAGE),
+            };
+            if !in_range {
+                return Err(EncodingError::PatchOutOfRange { patch: ix });
+            }
+        }
+        for (ix, run) in resources.glyph_runs.iter().enumerate() {
+            let offsets = &run.stream_offsets;
+            let in_range = run.glyphs.start <= run.glyphs.end
+                && run.glyphs.end <= resources.glyphs.len()
+                && run.normalized_coords.start <= run.normalized_coords.end
+                && run.normalized_coords.end <= resources.normalized_coords.len()
+                && offsets.path_tags <= self.path_tags.len()
+                && offsets.path_data <= self.path_data.len()
+                && offsets.draw_tags < self.draw_tags.len()
+                && offsets.draw_data <= self.draw_data.len()
+                && offsets.transforms <= self.transforms.len()
+                && offsets.styles <= self.styles.len();
+            if !in_range {
+                return Err(EncodingError::GlyphRunOutOfRange { glyph_run: ix });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use peniko::color::palette;
+    use peniko::kurbo::{Rect, Stroke};
+    use peniko::{BlendMode, ColorStop, Fill, Gradient};
+
+    use super::EncodingError;
+    use crate::{Encoding, PathTag, Transform};
+
+    fn rect() -> Rect {
+        Rect::new(0.0, 0.0, 10.0, 10.0)
+    }
+
+    fn valid_encoding() -> Encoding {
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_empty_shape();
+        encoding.encode_begin_clip(BlendMode::default(), 1.0);
+        encoding.encode_shape(&rect(), true);
+        encoding.encode_color(palette::css::RED);
+        assert!(encoding.encode_stroke_style(&Stroke::new(2.0)));
+        encoding.encode_shape(&rect(), false);
+        encoding.encode_brush(
+            &Gradient::new_linear((0.0, 0.0), (10.0, 0.0)).with_stops([
+                ColorStop::from((0.0, palette::css::RED)),
+                ColorStop::from((1.0, palette::css::BLUE)),
+            ]),
+            1.0,
+        );
+        encoding.encode_end_clip();
+        encoding
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(valid_encoding().validate(), Ok(()));
+        assert_eq!(Encoding::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn missing_transform_and_style() {
+        let mut encoding = Encoding::new();
+        encoding.encode_fill_style(Fill::NonZero);
+        encoding.encode_shape(&rect(), true);
+        assert_eq!(
+            encoding.validate(),
+            Err(EncodingError::MissingTransform { path_tag: 1 })
+        );
+        let mut encoding = Encoding::new();
+        encoding.encode_transform(Transform::IDENTITY);
+        encoding.encode_shape(&rect(), true);
+        assert_eq!(
+            encoding.validate(),
+            Err(EncodingError::MissingStyle { path_tag: 1 })
+        );
+    }
+
+    #[test]
+    fn stream_lengths() {
+        let mut encoding = valid_encoding();
+        encoding.path_data.pop();
+        assert!(matches!(
+            encoding.validate(),
+            Err(EncodingError::PathDataLength { .. })
+        ));
+        let mut encoding = valid_encoding();
+        encoding.draw_data.push(0);
+        assert!(matches!(
+            encoding.validate(),
+            Err(EncodingError::DrawDataLength { .. })
+        ));
+        let mut encoding = valid_encoding();
+        encoding.transforms.push(Transform::IDENTITY);
+        assert_eq!(
+            encoding.validate(),
+            Err(EncodingError::TransformCount {
+                expected: 1,
+                actual: 2
+            })
+        );
+    }
+
+    #[test]
+    fn counters() {
+        let mut encoding = valid_encoding();
+        encoding.n_paths += 1;
+        assert!(matches!(
+            encoding.validate(),
+            Err(EncodingError::PathCount { .. })
+        ));
+        let mut encoding = valid_encoding();
+        encoding.n_clips = 1;
+        assert_eq!(
+            encoding.validate(),
+            Err(EncodingError::ClipCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+        let mut encoding = valid_encoding();
+        encoding.encode_color(palette::css::RED);
+        assert!(matches!(
+            encoding.validate(),
+            Err(EncodingError::DrawPathMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn unbalanced_clips() {
+        let mut encoding = valid_encoding();
+        encoding.path_tags.push(PathTag::PATH);
+        encoding.n_paths += 1;
+        encoding.draw_tags.push(crate::DrawTag::END_CLIP);
+        encoding.n_clips += 1;
+        assert_eq!(
+            encoding.validate(),
+            Err(EncodingError::UnbalancedClip { draw_tag: 4 })
+        );
+    }
+
+    #[test]
+    fn patches() {
+        let mut encoding = valid_encoding();
+        if let crate::Patch::Ramp {
+            draw_data_offset, ..
+        } = &mut encoding.resources.patches[0]
+        {
+            *draw_data_offset -= 1;
+        }
+        assert_eq!(
+            encoding.validate(),
+            Err(EncodingError::PatchOutOfRange { patch: 0 })
+        );
+    }
+}